@@ -0,0 +1,49 @@
+use axum::{http::StatusCode, response::IntoResponse, routing::post, Router};
+use roboplc_rpc::{
+    response::{HandlerResponse, Response},
+    tools::{axum::RpcBody, http::HttpResponse},
+};
+use serde::{Deserialize, Serialize};
+
+#[derive(Serialize, Deserialize, Debug)]
+#[cfg_attr(
+    feature = "canonical",
+    serde(tag = "method", content = "params", deny_unknown_fields)
+)]
+#[cfg_attr(
+    not(feature = "canonical"),
+    serde(tag = "m", content = "p", deny_unknown_fields)
+)]
+enum MyMethod {
+    #[serde(rename = "hello")]
+    Hello { name: String },
+}
+
+fn handle_call(method: MyMethod) -> String {
+    match method {
+        MyMethod::Hello { name } => format!("Hello, {}", name),
+    }
+}
+
+async fn rpc(RpcBody(request): RpcBody<MyMethod>) -> axum::response::Response {
+    let (id, method) = request.into_parts();
+    let result = handle_call(method);
+    let Some(id) = id else {
+        // notifications have no response
+        return StatusCode::NO_CONTENT.into_response();
+    };
+    match HttpResponse::try_from(Response::from_parts(id, HandlerResponse::Ok(result))) {
+        Ok(response) => response.into_response(),
+        Err(e) => (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()).into_response(),
+    }
+}
+
+#[tokio::main]
+async fn main() {
+    let app = Router::new().route("/rpc", post(rpc));
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:3000")
+        .await
+        .unwrap();
+    // curl -d '{"i":1,"m":"hello","p":{"name":"world"}}' http://127.0.0.1:3000/rpc
+    axum::serve(listener, app).await.unwrap();
+}
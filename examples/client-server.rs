@@ -1,4 +1,7 @@
-use roboplc_rpc::{client::RpcClient, dataformat, server, RpcError, RpcErrorKind, RpcResult};
+use roboplc_rpc::{
+    client::RpcClient, dataformat, dataformat::DataFormat, field_names, response::Response, server,
+    RpcError, RpcErrorKind, RpcResult,
+};
 use serde::{Deserialize, Serialize};
 
 #[derive(Serialize, Deserialize, Debug)]
@@ -41,7 +44,7 @@ impl<'a> server::RpcServerHandler<'a> for MyRpc {
             MyMethod::Hello { name } => Ok(MyResult::String(format!("Hello, {}", name))),
             MyMethod::List { i } => Ok(MyResult::String(format!("List, {}", i))),
             MyMethod::Complicated {} => Err(RpcError::new(
-                RpcErrorKind::Custom(-32000),
+                RpcErrorKind::ServerError(-32000),
                 "Complicated method not implemented".into(),
             )),
         }
@@ -87,5 +90,65 @@ fn main() {
     if let Some(v) = resp {
         println!("response: {}", std::str::from_utf8(v.as_slice()).unwrap());
         dbg!(std::str::from_utf8(v.as_slice())).ok();
+        // the method is known, so the error is reported as invalid params (-32602)
+        let (_, result) = dataformat::Json::unpack::<Response<MyResult>>(&v)
+            .unwrap()
+            .into_parts();
+        assert_eq!(
+            result.err().map(RpcError::kind),
+            Some(RpcErrorKind::InvalidParams)
+        );
     }
+    // the request id is echoed in error responses for compact requests as well
+    let unknown_method_req = r#"{"i":4,"m":"unknown"}"#;
+    println!("request payload: {}", unknown_method_req);
+    let v = server
+        .handle_request_payload::<dataformat::Json>(unknown_method_req.as_bytes(), "local")
+        .expect("no response for a request with an id");
+    println!("response: {}", std::str::from_utf8(v.as_slice()).unwrap());
+    let response = dataformat::Json::unpack::<Response<MyResult>>(&v).unwrap();
+    assert_eq!(response.id_as_u32(), Some(4));
+    assert!(response.is_error());
+    // responses with neither result nor error are reported with a clear parse error
+    let req = client.request(MyMethod::Test {}).unwrap();
+    let err = req
+        .handle_response(br#"{"jsonrpc":"2.0","id":1}"#)
+        .unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        "response parse error: response missing result and error"
+    );
+    // handler errors can be compared as a whole or by kind only
+    let err = server::RpcServerHandler::handle_call(&MyRpc {}, MyMethod::Complicated {}, "local")
+        .unwrap_err();
+    assert_eq!(
+        err,
+        RpcError::new(
+            RpcErrorKind::ServerError(-32000),
+            "Complicated method not implemented".into()
+        )
+    );
+    assert_eq!(err, RpcErrorKind::ServerError(-32000));
+    // a batch of notifications gets no response
+    let payload = client
+        .notify_batch([MyMethod::Test {}, MyMethod::Hello { name: "world" }])
+        .unwrap();
+    println!(
+        "request payload: {}",
+        std::str::from_utf8(&payload).unwrap()
+    );
+    assert!(
+        server::collect_batch_responses(server.handle_json_batch_iter(&payload, "local")).is_none()
+    );
+    // wire field names follow the active layout
+    #[cfg(feature = "canonical")]
+    assert_eq!(
+        (field_names::ID, field_names::METHOD, field_names::PARAMS),
+        ("id", "method", "params")
+    );
+    #[cfg(not(feature = "canonical"))]
+    assert_eq!(
+        (field_names::ID, field_names::METHOD, field_names::PARAMS),
+        ("i", "m", "p")
+    );
 }
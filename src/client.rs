@@ -1,29 +1,51 @@
 use core::{
+    fmt,
     marker::PhantomData,
     mem,
     sync::atomic::{AtomicU32, Ordering},
 };
 
-use serde::{Deserialize, Serialize};
+use std::{
+    collections::BTreeSet,
+    io,
+    sync::{Mutex, MutexGuard, OnceLock, PoisonError},
+    time::Instant,
+};
+
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
 
-use crate::{dataformat, request::Request, response::Response, RpcError, RpcErrorKind, RpcResult};
+use crate::{
+    dataformat,
+    request::{MethodPeek, Request},
+    response::{Response, ResponseChunk, ResultPeek},
+    Id, RpcError, RpcErrorKind, RpcId, RpcResult,
+};
 
 #[allow(clippy::module_name_repetitions)]
 #[derive(Default)]
-/// RPC client module, used to create RPC requests and handle RPC responses, call ids are `u32`
-pub struct RpcClient<'a, D, M, R> {
+/// RPC client module, used to create RPC requests and handle RPC responses, call ids are
+/// generated from a `u32` counter and converted into the id type `I` (see [`RpcId`]). The counter
+/// wraps to zero after `u32::MAX`, use [`RpcClient::request_checked`] to prevent id reuse
+pub struct RpcClient<'a, D, M, R, I = Id> {
     _phantom_d: PhantomData<D>,
     _phantom_a: PhantomData<&'a ()>,
     _phantom_m: PhantomData<M>,
     _phantom_r: PhantomData<R>,
+    _phantom_i: PhantomData<I>,
     request_id: AtomicU32,
+    id_generator: Option<IdGenerator>,
 }
 
-impl<'a, D, M, R> RpcClient<'a, D, M, R>
+const ERR_MISSING_RESULT: &str = "response missing result and error";
+
+type IdGenerator = Box<dyn Fn() -> u32 + Send + Sync>;
+
+impl<'a, D, M, R, I> RpcClient<'a, D, M, R, I>
 where
     D: dataformat::DataFormat,
     M: Serialize + Deserialize<'a>,
     R: Serialize + Deserialize<'a>,
+    I: RpcId,
 {
     /// Create a new RPC client
     pub fn new() -> Self {
@@ -32,49 +54,300 @@ where
             _phantom_a: PhantomData,
             _phantom_m: PhantomData,
             _phantom_r: PhantomData,
+            _phantom_i: PhantomData,
             request_id: AtomicU32::new(0),
+            id_generator: None,
+        }
+    }
+    /// Use a custom call id generator instead of the default counter (which starts from zero),
+    /// e.g. to seed ids from a random base or a persisted counter. The generator is responsible
+    /// for id uniqueness: [`RpcClient::current_id`] keeps returning the unused counter value and
+    /// [`RpcClient::request_checked`] does not check generated ids
+    pub fn with_id_generator(
+        mut self,
+        generator: impl Fn() -> u32 + Send + Sync + 'static,
+    ) -> Self {
+        self.id_generator = Some(Box::new(generator));
+        self
+    }
+    fn next_id(&self) -> u32 {
+        if let Some(generator) = &self.id_generator {
+            generator()
+        } else {
+            self.request_id.fetch_add(1, Ordering::SeqCst)
         }
     }
-    /// Create a new RPC request
-    pub fn request(&self, method: M) -> Result<RpcClientRequest<D, M, R>, D::PackError> {
-        let id = self.request_id.fetch_add(1, Ordering::SeqCst);
-        let req = Request::new(id.into(), method);
+    /// Get the current counter value (the id of the next request, a custom id generator is not
+    /// taken into account)
+    pub fn current_id(&self) -> u32 {
+        self.request_id.load(Ordering::SeqCst)
+    }
+    /// Create a new RPC request, returns [`RequestError::IdsExhausted`] instead of wrapping the id
+    /// counter when it reaches `u32::MAX` (a custom id generator is called as-is)
+    pub fn request_checked(
+        &self,
+        method: M,
+    ) -> Result<RpcClientRequest<D, M, R, I>, RequestError<D::PackError>> {
+        let id = if let Some(generator) = &self.id_generator {
+            generator()
+        } else {
+            self.request_id
+                .fetch_update(Ordering::SeqCst, Ordering::SeqCst, |id| id.checked_add(1))
+                .map_err(|_| RequestError::IdsExhausted)?
+        };
+        self.request_with_id(id, method).map_err(RequestError::Pack)
+    }
+    /// Create a new RPC request. The id counter wraps around to zero after `u32::MAX` (use
+    /// [`RpcClient::request_checked`] to get an error instead)
+    pub fn request(&self, method: M) -> Result<RpcClientRequest<D, M, R, I>, D::PackError> {
+        let id = self.next_id();
+        let req = Request::<M, I>::new(I::from_u32(id), method);
+        let payload = D::pack(&req)?;
+        Ok(RpcClientRequest::new(Some(id), payload))
+    }
+    /// Create a new RPC request, packed into the buffer (the buffer is cleared first, its capacity
+    /// is reused to avoid allocations). Returns the call id to handle the response with
+    /// [`RpcClient::handle_response`]
+    pub fn request_into(&self, method: M, buf: &mut Vec<u8>) -> Result<u32, D::PackError> {
+        let id = self.next_id();
+        let req = Request::<M, I>::new(I::from_u32(id), method);
+        buf.clear();
+        D::pack_into(&req, buf)?;
+        Ok(id)
+    }
+    /// Handle the response payload of a call with the given id (e.g. created with
+    /// [`RpcClient::request_into`])
+    pub fn handle_response(
+        &self,
+        id: u32,
+        response_payload: &'a [u8],
+    ) -> Result<R, ClientError<I>> {
+        handle_response::<D, R, I>(Some(I::from_u32(id)), response_payload)
+    }
+    /// Create a new RPC request with the given id instead of the counter value (e.g. when
+    /// resuming a session or replaying a log). The caller is responsible for id uniqueness
+    pub fn request_with_id(
+        &self,
+        id: u32,
+        method: M,
+    ) -> Result<RpcClientRequest<D, M, R, I>, D::PackError> {
+        let req = Request::<M, I>::new(I::from_u32(id), method);
         let payload = D::pack(&req)?;
         Ok(RpcClientRequest::new(Some(id), payload))
     }
-    /// Create a new RPC request with no id (no response expected)
-    pub fn request0(&self, method: M) -> Result<RpcClientRequest<D, M, R>, D::PackError> {
-        let req = Request::new0(method);
+    #[cfg(feature = "meta")]
+    /// Create a new RPC request with metadata (e.g. a trace id or an auth token, see
+    /// [`Request::with_meta`])
+    pub fn request_with_meta(
+        &self,
+        method: M,
+        meta: serde_json::Value,
+    ) -> Result<RpcClientRequest<D, M, R, I>, D::PackError> {
+        let id = self.next_id();
+        let req = Request::<M, I>::new(I::from_u32(id), method).with_meta(meta);
+        let payload = D::pack(&req)?;
+        Ok(RpcClientRequest::new(Some(id), payload))
+    }
+    /// Create a new RPC notification (a request with no id, no response expected)
+    pub fn request0(&self, method: M) -> Result<RpcClientNotification<D, M>, D::PackError> {
+        let req = Request::<M, I>::new0(method);
+        let payload = D::pack(&req)?;
+        Ok(RpcClientNotification::new(payload))
+    }
+    /// Decode a notification pushed by the server (a request with no id) and return its method.
+    /// Requests with an id are rejected as they are server calls which expect a response
+    pub fn decode_notification(&self, payload: &'a [u8]) -> Result<M, RpcError> {
+        let (id, method) = D::unpack::<Request<M, I>>(payload)
+            .map_err(|e| RpcError::from_display(RpcErrorKind::ParseError, &e))?
+            .into_parts();
+        if id.is_some() {
+            return Err(RpcError::new(
+                RpcErrorKind::InvalidRequest,
+                "not a notification: the request has an id".to_owned(),
+            ));
+        }
+        Ok(method)
+    }
+    /// Create a new RPC batch request. Each call gets a fresh id, notifications (see
+    /// [`BatchCall::Notification`]) are sent with no id
+    pub fn batch<C>(
+        &self,
+        methods: impl IntoIterator<Item = C>,
+    ) -> Result<RpcClientBatchRequest<D, M, R, I>, D::PackError>
+    where
+        C: Into<BatchCall<M>>,
+    {
+        let mut ids = Vec::new();
+        let requests: Vec<Request<M, I>> = methods
+            .into_iter()
+            .map(|call| match call.into() {
+                BatchCall::Call(method) => {
+                    let id = self.next_id();
+                    ids.push((id, I::from_u32(id)));
+                    Request::new(I::from_u32(id), method)
+                }
+                BatchCall::Notification(method) => Request::new0(method),
+            })
+            .collect();
+        let payload = D::pack(&requests)?;
+        Ok(RpcClientBatchRequest::new(ids, payload))
+    }
+    /// Pack a batch of notifications (requests with no ids), no responses are expected, so the
+    /// payload only is returned
+    pub fn notify_batch(
+        &self,
+        methods: impl IntoIterator<Item = M>,
+    ) -> Result<Vec<u8>, D::PackError> {
+        let requests: Vec<Request<M, I>> = methods.into_iter().map(Request::new0).collect();
+        D::pack(&requests)
+    }
+}
+
+impl<'a, D, M, R, I> RpcClient<'a, D, M, R, I>
+where
+    D: dataformat::DataFormat,
+    M: Serialize + Deserialize<'a>,
+    R: Serialize + DeserializeOwned,
+    I: RpcId,
+{
+    /// Create a request, send it via the transport and handle the response
+    pub fn call(&self, method: M, transport: &impl Transport) -> RpcResult<R> {
+        let request = self
+            .request(method)
+            .map_err(|e| RpcError::from_display(RpcErrorKind::InternalError, &e))?;
+        let response = transport
+            .call(request.payload())
+            .map_err(|e| RpcError::from_display(RpcErrorKind::InternalError, &e))?;
+        handle_response::<D, R, I>(request.call_id(), &response).map_err(Into::into)
+    }
+}
+
+#[cfg(feature = "uuid")]
+impl<'a, D, M, R> RpcClient<'a, D, M, R, Id>
+where
+    D: dataformat::DataFormat,
+    M: Serialize + Deserialize<'a>,
+    R: Serialize + Deserialize<'a>,
+{
+    /// Create a new RPC request with a random (v4) UUID string id, which is globally unique
+    /// across clients. The response id is compared with the UUID string
+    pub fn request_uuid(&self, method: M) -> Result<RpcClientRequest<D, M, R>, D::PackError> {
+        let id = Id::String(uuid::Uuid::new_v4().to_string());
+        let req = Request::<M>::new(id.clone(), method);
         let payload = D::pack(&req)?;
-        Ok(RpcClientRequest::new(None, payload))
+        let mut request = RpcClientRequest::new(None, payload);
+        request.custom_id = Some(id);
+        Ok(request)
+    }
+}
+
+/// Create a new RPC client, a shortcut for `RpcClient::<D, M, R>::new()` with the default id
+/// type, e.g. `let client = new_for::<Json, MyMethod, MyResult>();`
+pub fn new_for<'a, D, M, R>() -> RpcClient<'a, D, M, R>
+where
+    D: dataformat::DataFormat,
+    M: Serialize + Deserialize<'a>,
+    R: Serialize + Deserialize<'a>,
+{
+    RpcClient::new()
+}
+
+/// Create a new JSON RPC client, only method and result types are required, e.g.
+/// `let client = json_client::<MyMethod, MyResult>();` (can be inferred from the usage as well)
+pub fn json_client<'a, M, R>() -> RpcClient<'a, dataformat::Json, M, R>
+where
+    M: Serialize + Deserialize<'a>,
+    R: Serialize + Deserialize<'a>,
+{
+    RpcClient::new()
+}
+
+/// Batch request item, methods are converted into calls automatically
+pub enum BatchCall<M> {
+    /// A call (a response is expected)
+    Call(M),
+    /// A notification (no response expected)
+    Notification(M),
+}
+
+impl<M> From<M> for BatchCall<M> {
+    fn from(method: M) -> Self {
+        BatchCall::Call(method)
     }
 }
 
 /// RPC client request, no need to create directly if `RpcClient` is used
-pub struct RpcClientRequest<D, M, R> {
+pub struct RpcClientRequest<D, M, R, I = Id> {
     id: Option<u32>,
+    custom_id: Option<I>,
+    method_name: OnceLock<Option<String>>,
     payload: Vec<u8>,
+    deadline: Option<Instant>,
     phantom_d: core::marker::PhantomData<D>,
     phantom_m: core::marker::PhantomData<M>,
     phantom_r: core::marker::PhantomData<R>,
+    phantom_i: core::marker::PhantomData<I>,
+}
+
+impl<D, M, R, I: RpcId> RpcClientRequest<D, M, R, I> {
+    /// Get the request call id as sent on the wire (`None` for notifications)
+    pub fn call_id(&self) -> Option<I> {
+        self.custom_id.clone().or_else(|| self.id.map(I::from_u32))
+    }
 }
 
-impl<'a, D, M, R> RpcClientRequest<D, M, R>
+impl<'a, D, M, R, I> RpcClientRequest<D, M, R, I>
 where
     D: dataformat::DataFormat,
     M: Serialize + Deserialize<'a>,
     R: Serialize + Deserialize<'a>,
+    I: RpcId,
 {
     /// Create a new RPC client request
     pub fn new(id: Option<u32>, payload: Vec<u8>) -> Self {
         Self {
             id,
+            custom_id: None,
+            method_name: OnceLock::new(),
             payload,
+            deadline: None,
             phantom_d: core::marker::PhantomData,
             phantom_m: core::marker::PhantomData,
             phantom_r: core::marker::PhantomData,
+            phantom_i: core::marker::PhantomData,
         }
     }
+    /// Set the request deadline (bookkeeping only, the client does not wait for responses)
+    pub fn with_deadline(mut self, deadline: Instant) -> Self {
+        self.deadline = Some(deadline);
+        self
+    }
+    /// Get the request deadline
+    pub fn deadline(&self) -> Option<Instant> {
+        self.deadline
+    }
+    /// Returns `true` if the request deadline is set and has passed
+    pub fn is_expired(&self, now: Instant) -> bool {
+        match self.deadline {
+            Some(deadline) => now >= deadline,
+            None => false,
+        }
+    }
+    /// Get the request call id (`None` for notifications and calls with non-numeric ids)
+    pub fn id(&self) -> Option<u32> {
+        self.id
+    }
+    /// Get the method name (e.g. for logging), parsed from the payload on the first call (`None`
+    /// if the payload has been taken before, see [`RpcClientRequest::take_payload`])
+    pub fn method_name(&self) -> Option<&str> {
+        self.method_name
+            .get_or_init(|| {
+                D::unpack::<MethodPeek>(&self.payload)
+                    .ok()
+                    .and_then(|peek| peek.method.map(Into::into))
+            })
+            .as_deref()
+    }
     /// Get the request payload
     pub fn payload(&self) -> &[u8] {
         &self.payload
@@ -84,28 +357,806 @@ where
         mem::take(&mut self.payload)
     }
     /// Handle the response payload
-    pub fn handle_response(&self, response_payload: &'a [u8]) -> RpcResult<R> {
-        let Some(id) = self.id else {
-            return Err(RpcError {
-                kind: RpcErrorKind::InvalidRequest,
-                message: Some("request ID is missing".to_owned()),
-            });
+    pub fn handle_response(&self, response_payload: &'a [u8]) -> Result<R, ClientError<I>> {
+        handle_response::<D, R, I>(self.call_id(), response_payload)
+    }
+    /// Handle a streamed response chunk payload (see [`ResponseChunk`]), returns the chunk
+    /// sequence number, the last chunk flag and the chunk data
+    pub fn handle_chunk(&self, chunk_payload: &'a [u8]) -> Result<(u64, bool, R), RpcError> {
+        let Some(expected) = self.call_id() else {
+            return Err(ClientError::<I>::MissingId.into());
         };
-        match D::unpack::<Response<R>>(response_payload) {
-            Ok(r) => {
-                let (res_id, res) = r.into_parts();
-                if res_id != id {
-                    return Err(RpcError {
-                        kind: RpcErrorKind::InvalidRequest,
-                        message: Some("response ID does not match request ID".to_owned()),
-                    });
-                }
-                res.into()
+        let (id, seq, last, res) = D::unpack::<ResponseChunk<R, I>>(chunk_payload)
+            .map_err(|e| RpcError::from_display(RpcErrorKind::ParseError, &e))?
+            .into_parts();
+        if id != expected {
+            return Err(ClientError::IdMismatch { expected, got: id }.into());
+        }
+        RpcResult::from(res).map(|data| (seq, last, data))
+    }
+}
+
+fn handle_response<'a, D, R, I>(
+    expected: Option<I>,
+    response_payload: &'a [u8],
+) -> Result<R, ClientError<I>>
+where
+    D: dataformat::DataFormat,
+    R: Deserialize<'a>,
+    I: RpcId,
+{
+    let Some(expected) = expected else {
+        return Err(ClientError::MissingId);
+    };
+    match D::unpack::<Response<R, I>>(response_payload) {
+        Ok(r) => {
+            let (res_id, res) = r.into_parts();
+            if res_id != expected {
+                return Err(ClientError::IdMismatch {
+                    expected,
+                    got: res_id,
+                });
+            }
+            RpcResult::from(res).map_err(ClientError::Rpc)
+        }
+        Err(e) => Err(ClientError::Parse(parse_error_message::<D>(
+            response_payload,
+            &e,
+        ))),
+    }
+}
+
+/// Replaces opaque de-serialization errors of responses with neither result nor error with a
+/// clear message
+fn parse_error_message<D: dataformat::DataFormat>(
+    response_payload: &[u8],
+    error: &impl fmt::Display,
+) -> String {
+    match D::unpack::<ResultPeek>(response_payload) {
+        Ok(peek) if peek.is_empty() => ERR_MISSING_RESULT.to_owned(),
+        _ => error.to_string(),
+    }
+}
+
+/// Synchronous request/response transport (e.g. a TCP socket), used by [`RpcClient::call`].
+/// Implemented for closures as well
+pub trait Transport {
+    /// Send the request payload and receive the response payload
+    fn call(&self, payload: &[u8]) -> io::Result<Vec<u8>>;
+}
+
+impl<F> Transport for F
+where
+    F: Fn(&[u8]) -> io::Result<Vec<u8>>,
+{
+    fn call(&self, payload: &[u8]) -> io::Result<Vec<u8>> {
+        self(payload)
+    }
+}
+
+/// Decode a response payload into the call result, the response id is ignored (e.g. for strict
+/// request/response transports where responses are not correlated)
+pub fn decode_result<'a, D, R>(response_payload: &'a [u8]) -> RpcResult<R>
+where
+    D: dataformat::DataFormat,
+    R: Deserialize<'a>,
+{
+    D::unpack::<Response<R>>(response_payload)
+        .map_err(|e| {
+            RpcError::new(
+                RpcErrorKind::ParseError,
+                parse_error_message::<D>(response_payload, &e),
+            )
+        })?
+        .into_parts()
+        .1
+        .into()
+}
+
+/// Send a request and handle the response, retrying up to `attempts` times if no response is
+/// received (`send` returns `None`), or the response can not be parsed or correlated. The same
+/// payload is re-sent on each attempt. Errors returned by the server are not retried
+pub fn retry<D, M, R, I>(
+    request: &RpcClientRequest<D, M, R, I>,
+    attempts: usize,
+    mut send: impl FnMut(&[u8]) -> Option<Vec<u8>>,
+) -> RpcResult<R>
+where
+    D: dataformat::DataFormat,
+    R: DeserializeOwned,
+    I: RpcId,
+{
+    let mut last_error = RpcError::new(
+        RpcErrorKind::InternalError,
+        "no response received".to_owned(),
+    );
+    for _ in 0..attempts {
+        let Some(response) = send(&request.payload) else {
+            continue;
+        };
+        match handle_response::<D, R, I>(request.call_id(), &response) {
+            Ok(result) => return Ok(result),
+            Err(ClientError::Rpc(e)) => return Err(e),
+            Err(e @ ClientError::MissingId) => return Err(e.into()),
+            Err(e) => last_error = e.into(),
+        }
+    }
+    Err(last_error)
+}
+
+/// Client-side response handling error
+#[allow(clippy::module_name_repetitions)]
+#[derive(Debug)]
+pub enum ClientError<I = Id> {
+    /// The response payload can not be parsed
+    Parse(String),
+    /// The response id does not match the request id
+    IdMismatch {
+        /// The request id
+        expected: I,
+        /// The response id
+        got: I,
+    },
+    /// The request has no id (a notification), no response is expected
+    MissingId,
+    /// The server returned an error
+    Rpc(RpcError),
+}
+
+impl<I: fmt::Debug> fmt::Display for ClientError<I> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ClientError::Parse(e) => write!(f, "response parse error: {}", e),
+            ClientError::IdMismatch { expected, got } => write!(
+                f,
+                "response ID does not match request ID (expected {:?}, got {:?})",
+                expected, got
+            ),
+            ClientError::MissingId => write!(f, "request ID is missing"),
+            ClientError::Rpc(e) => write!(f, "{}", e),
+        }
+    }
+}
+
+impl<I: fmt::Debug> std::error::Error for ClientError<I> {}
+
+impl<I> From<ClientError<I>> for RpcError {
+    fn from(err: ClientError<I>) -> Self {
+        match err {
+            ClientError::Parse(message) => RpcError::new(RpcErrorKind::ParseError, message),
+            ClientError::IdMismatch { .. } => RpcError::new(
+                RpcErrorKind::InvalidRequest,
+                "response ID does not match request ID".to_owned(),
+            ),
+            ClientError::MissingId => RpcError::new(
+                RpcErrorKind::InvalidRequest,
+                "request ID is missing".to_owned(),
+            ),
+            ClientError::Rpc(e) => e,
+        }
+    }
+}
+
+/// Checked request creation error
+#[allow(clippy::module_name_repetitions)]
+#[derive(Debug)]
+pub enum RequestError<E> {
+    /// The request can not be packed
+    Pack(E),
+    /// The id counter has reached `u32::MAX`
+    IdsExhausted,
+}
+
+impl<E: fmt::Display> fmt::Display for RequestError<E> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RequestError::Pack(e) => write!(f, "request pack error: {}", e),
+            RequestError::IdsExhausted => write!(f, "request ID counter is exhausted"),
+        }
+    }
+}
+
+impl<E: fmt::Debug + fmt::Display> std::error::Error for RequestError<E> {}
+
+/// RPC client notification, created with [`RpcClient::request0`]. No response is expected
+pub struct RpcClientNotification<D, M> {
+    payload: Vec<u8>,
+    phantom_d: PhantomData<D>,
+    phantom_m: PhantomData<M>,
+}
+
+impl<D, M> RpcClientNotification<D, M>
+where
+    D: dataformat::DataFormat,
+{
+    fn new(payload: Vec<u8>) -> Self {
+        Self {
+            payload,
+            phantom_d: PhantomData,
+            phantom_m: PhantomData,
+        }
+    }
+    /// Get the notification payload
+    pub fn payload(&self) -> &[u8] {
+        &self.payload
+    }
+    /// Take the notification payload
+    pub fn take_payload(&mut self) -> Vec<u8> {
+        mem::take(&mut self.payload)
+    }
+}
+
+/// RPC client batch request, created with [`RpcClient::batch`]
+pub struct RpcClientBatchRequest<D, M, R, I = Id> {
+    ids: Vec<(u32, I)>,
+    payload: Vec<u8>,
+    phantom_d: PhantomData<D>,
+    phantom_m: PhantomData<M>,
+    phantom_r: PhantomData<R>,
+}
+
+impl<'a, D, M, R, I> RpcClientBatchRequest<D, M, R, I>
+where
+    D: dataformat::DataFormat,
+    M: Serialize + Deserialize<'a>,
+    R: Serialize + Deserialize<'a>,
+    I: RpcId,
+{
+    fn new(ids: Vec<(u32, I)>, payload: Vec<u8>) -> Self {
+        Self {
+            ids,
+            payload,
+            phantom_d: PhantomData,
+            phantom_m: PhantomData,
+            phantom_r: PhantomData,
+        }
+    }
+    /// Get the request payload
+    pub fn payload(&self) -> &[u8] {
+        &self.payload
+    }
+    /// Take the request payload
+    pub fn take_payload(&mut self) -> Vec<u8> {
+        mem::take(&mut self.payload)
+    }
+    /// Handle the batch response payload. The results are correlated by call ids and returned in
+    /// the order of the response, responses with unknown ids are ignored. If the payload can not
+    /// be parsed, a parse error is returned for each call
+    pub fn handle_response(&self, response_payload: &'a [u8]) -> Vec<(u32, RpcResult<R>)> {
+        match D::unpack::<Vec<Response<R, I>>>(response_payload) {
+            Ok(responses) => responses
+                .into_iter()
+                .filter_map(|r| {
+                    let (res_id, res) = r.into_parts();
+                    self.ids
+                        .iter()
+                        .find(|(_, id)| *id == res_id)
+                        .map(|(id, _)| (*id, res.into()))
+                })
+                .collect(),
+            Err(e) => {
+                let message = e.to_string();
+                self.ids
+                    .iter()
+                    .map(|(id, _)| {
+                        (
+                            *id,
+                            Err(RpcError::new(RpcErrorKind::ParseError, message.clone())),
+                        )
+                    })
+                    .collect()
+            }
+        }
+    }
+    /// Find the position of a call in the batch by its id (notifications are not counted)
+    pub fn find(&self, id: u32) -> Option<usize> {
+        self.ids.iter().position(|(call_id, _)| *call_id == id)
+    }
+    /// Get call ids which are shared by more than one call in the batch (e.g. produced by a
+    /// custom id generator)
+    pub fn duplicate_ids(&self) -> Vec<u32> {
+        let mut duplicates: Vec<u32> = Vec::new();
+        for (pos, (id, _)) in self.ids.iter().enumerate() {
+            if !duplicates.contains(id) && self.ids[pos + 1..].iter().any(|(i, _)| i == id) {
+                duplicates.push(*id);
+            }
+        }
+        duplicates
+    }
+    /// Handle the batch response payload. The results are returned in the order of the calls in
+    /// the request, a parse error is returned for calls with no response. If several calls share
+    /// the same id (see [`Self::duplicate_ids`]), responses with the id are assigned to the calls
+    /// in order, so all of them are surfaced, the ids of such calls are flagged with
+    /// [`BatchCallId::DuplicateId`]
+    pub fn into_results(
+        self,
+        response_payload: &'a [u8],
+    ) -> impl Iterator<Item = (BatchCallId, RpcResult<R>)> {
+        let mut results: Vec<Option<RpcResult<R>>> = self.ids.iter().map(|_| None).collect();
+        for (id, result) in self.handle_response(response_payload) {
+            let slot = self
+                .ids
+                .iter()
+                .zip(results.iter_mut())
+                .find(|((call_id, _), slot)| *call_id == id && slot.is_none());
+            if let Some((_, slot)) = slot {
+                *slot = Some(result);
             }
-            Err(e) => Err(RpcError {
-                kind: RpcErrorKind::ParseError,
-                message: Some(e.to_string()),
+        }
+        batch_results(&self.ids, results)
+    }
+    /// Create a collector for batch responses which arrive as individual frames
+    pub fn collector(&self) -> BatchCollector<D, R, I> {
+        BatchCollector {
+            ids: self.ids.clone(),
+            results: self.ids.iter().map(|_| None).collect(),
+            phantom_d: PhantomData,
+        }
+    }
+}
+
+/// Collects batch call responses which arrive as individual frames rather than one array (see
+/// [`RpcClientBatchRequest::collector`])
+pub struct BatchCollector<D, R, I = Id> {
+    ids: Vec<(u32, I)>,
+    results: Vec<Option<RpcResult<R>>>,
+    phantom_d: PhantomData<D>,
+}
+
+impl<D, R, I> BatchCollector<D, R, I>
+where
+    D: dataformat::DataFormat,
+    R: DeserializeOwned,
+    I: RpcId,
+{
+    /// Handle a single response payload, returns the call id. An error is returned if the
+    /// payload can not be parsed or no call with the response id is pending
+    pub fn push(&mut self, response_payload: &[u8]) -> Result<u32, RpcError> {
+        let (res_id, res) = D::unpack::<Response<R, I>>(response_payload)
+            .map_err(|e| RpcError::from_display(RpcErrorKind::ParseError, &e))?
+            .into_parts();
+        let slot = self
+            .ids
+            .iter()
+            .zip(self.results.iter_mut())
+            .find(|((_, id), slot)| *id == res_id && slot.is_none());
+        let Some(((id, _), slot)) = slot else {
+            return Err(RpcError::new(
+                RpcErrorKind::InvalidRequest,
+                format!("no pending call with ID {:?}", res_id),
+            ));
+        };
+        *slot = Some(res.into());
+        Ok(*id)
+    }
+    /// Call ids which have no response yet
+    pub fn pending(&self) -> Vec<u32> {
+        self.ids
+            .iter()
+            .zip(&self.results)
+            .filter(|(_, result)| result.is_none())
+            .map(|((id, _), _)| *id)
+            .collect()
+    }
+    /// Returns `true` if all calls have got responses
+    pub fn is_complete(&self) -> bool {
+        self.results.iter().all(Option::is_some)
+    }
+    /// Get the results in the order of the calls in the request, a parse error is returned for
+    /// calls with no response (see [`RpcClientBatchRequest::into_results`])
+    pub fn into_results(self) -> impl Iterator<Item = (BatchCallId, RpcResult<R>)> {
+        batch_results(&self.ids, self.results)
+    }
+}
+
+/// Call id of a batch call result
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BatchCallId {
+    /// The id is unique in the batch
+    Unique(u32),
+    /// The id is shared by several calls in the batch (e.g. produced by a custom id generator),
+    /// responses with the id are assigned to the calls in order
+    DuplicateId(u32),
+}
+
+impl BatchCallId {
+    /// Get the call id
+    pub fn id(self) -> u32 {
+        match self {
+            BatchCallId::Unique(id) | BatchCallId::DuplicateId(id) => id,
+        }
+    }
+}
+
+fn batch_results<R, I>(
+    ids: &[(u32, I)],
+    results: Vec<Option<RpcResult<R>>>,
+) -> impl Iterator<Item = (BatchCallId, RpcResult<R>)> {
+    let ids: Vec<BatchCallId> = ids
+        .iter()
+        .map(|(id, _)| {
+            if ids.iter().filter(|(i, _)| i == id).count() > 1 {
+                BatchCallId::DuplicateId(*id)
+            } else {
+                BatchCallId::Unique(*id)
+            }
+        })
+        .collect();
+    ids.into_iter().zip(results).map(|(id, result)| {
+        (
+            id,
+            result.unwrap_or_else(|| {
+                Err(RpcError::new(
+                    RpcErrorKind::ParseError,
+                    "no response for the call".to_owned(),
+                ))
             }),
+        )
+    })
+}
+
+/// Registry of pending call ids, used to correlate responses which arrive interleaved over a
+/// single transport. Can be shared between threads
+pub struct PendingRequests<D, R, I = Id> {
+    ids: Mutex<BTreeSet<u32>>,
+    phantom_d: PhantomData<D>,
+    phantom_r: PhantomData<R>,
+    phantom_i: PhantomData<I>,
+}
+
+impl<D, R, I> Default for PendingRequests<D, R, I> {
+    fn default() -> Self {
+        Self {
+            ids: <_>::default(),
+            phantom_d: PhantomData,
+            phantom_r: PhantomData,
+            phantom_i: PhantomData,
+        }
+    }
+}
+
+impl<'a, D, R, I> PendingRequests<D, R, I>
+where
+    D: dataformat::DataFormat,
+    R: Serialize + Deserialize<'a>,
+    I: RpcId,
+{
+    /// Create a new empty registry
+    pub fn new() -> Self {
+        Self::default()
+    }
+    fn ids(&self) -> MutexGuard<'_, BTreeSet<u32>> {
+        // the set is always consistent, a panic in another thread does not poison it
+        self.ids.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+    /// Register a call id, returns `false` if the id is already pending
+    pub fn insert(&self, id: u32) -> bool {
+        self.ids().insert(id)
+    }
+    /// Register a client request, returns `false` if the request is a notification, has a
+    /// non-numeric id or its id is already pending
+    pub fn register<M>(&self, request: &RpcClientRequest<D, M, R, I>) -> bool {
+        match request.id {
+            Some(id) => self.insert(id),
+            None => false,
         }
     }
+    /// Remove a call id (e.g. on timeout), returns `false` if the id is not pending
+    pub fn remove(&self, id: u32) -> bool {
+        self.ids().remove(&id)
+    }
+    /// Number of pending calls
+    pub fn len(&self) -> usize {
+        self.ids().len()
+    }
+    /// Returns `true` if there are no pending calls
+    pub fn is_empty(&self) -> bool {
+        self.ids().is_empty()
+    }
+    /// Handle a response payload of any pending call. On success, the call id is removed from
+    /// the registry and returned together with the call result. An error is returned if the
+    /// payload can not be parsed or the response id is not pending
+    pub fn dispatch_response(
+        &self,
+        response_payload: &'a [u8],
+    ) -> Result<(u32, RpcResult<R>), RpcError> {
+        let response = D::unpack::<Response<R, I>>(response_payload)
+            .map_err(|e| RpcError::from_display(RpcErrorKind::ParseError, &e))?;
+        let (res_id, res) = response.into_parts();
+        let mut ids = self.ids();
+        // ids which can not be converted back into u32 (e.g. custom string ids) are matched
+        // by value
+        let id = match res_id.as_u32() {
+            Some(id) => ids.contains(&id).then_some(id),
+            None => ids.iter().copied().find(|id| I::from_u32(*id) == res_id),
+        };
+        let Some(id) = id else {
+            return Err(RpcError::new(
+                RpcErrorKind::InvalidRequest,
+                format!(
+                    "response ID {:?} does not match any pending request",
+                    res_id
+                ),
+            ));
+        };
+        ids.remove(&id);
+        Ok((id, res.into()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use core::time::Duration;
+
+    use super::*;
+    use crate::{
+        dataformat::{DataFormat, Json},
+        response::HandlerResponse,
+    };
+
+    #[derive(Serialize, Deserialize, Debug)]
+    #[cfg_attr(
+        feature = "canonical",
+        serde(tag = "method", content = "params", deny_unknown_fields)
+    )]
+    #[cfg_attr(
+        not(feature = "canonical"),
+        serde(tag = "m", content = "p", deny_unknown_fields)
+    )]
+    enum TestMethod {
+        #[serde(rename = "test")]
+        Test {},
+        #[serde(rename = "add")]
+        Add { a: i64, b: i64 },
+    }
+
+    fn response_payload<I: RpcId>(id: I, result: RpcResult<i64>) -> Vec<u8> {
+        Json::pack(&Response::from_handler_response(
+            id,
+            HandlerResponse::from(result),
+        ))
+        .unwrap()
+    }
+
+    #[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+    struct SessionId(std::string::String);
+
+    impl RpcId for SessionId {
+        fn from_u32(value: u32) -> Self {
+            SessionId(format!("s-{}", value))
+        }
+    }
+
+    #[test]
+    fn test_custom_id_type() {
+        let client: RpcClient<Json, TestMethod, i64, SessionId> = RpcClient::new();
+        let req = client.request(TestMethod::Add { a: 1, b: 2 }).unwrap();
+        assert_eq!(req.call_id(), Some(SessionId("s-0".to_owned())));
+        let (id, _) = Json::unpack::<Request<TestMethod, SessionId>>(req.payload())
+            .unwrap()
+            .into_parts();
+        assert_eq!(id, Some(SessionId("s-0".to_owned())));
+        let response = response_payload(SessionId("s-0".to_owned()), Ok(3));
+        assert_eq!(req.handle_response(&response).unwrap(), 3);
+        let response = response_payload(SessionId("s-1".to_owned()), Ok(3));
+        assert!(matches!(
+            req.handle_response(&response),
+            Err(ClientError::IdMismatch { .. })
+        ));
+    }
+
+    #[test]
+    fn test_pending_requests() {
+        let client: RpcClient<Json, TestMethod, i64> = RpcClient::new();
+        let pending = PendingRequests::<Json, i64>::new();
+        let first = client.request(TestMethod::Test {}).unwrap();
+        let second = client.request(TestMethod::Add { a: 1, b: 2 }).unwrap();
+        assert!(pending.register(&first));
+        assert!(pending.register(&second));
+        assert!(!pending.register(&second));
+        let (id, result) = pending
+            .dispatch_response(&response_payload(Id::from(1), Ok(3)))
+            .unwrap();
+        assert_eq!((id, result.unwrap()), (1, 3));
+        assert_eq!(pending.len(), 1);
+        // the id is no longer pending
+        assert!(pending
+            .dispatch_response(&response_payload(Id::from(1), Ok(3)))
+            .is_err());
+        assert!(pending
+            .dispatch_response(&response_payload(Id::from("0"), Ok(0)))
+            .is_err());
+        let custom = PendingRequests::<Json, i64, SessionId>::new();
+        assert!(custom.insert(5));
+        let (id, _) = custom
+            .dispatch_response(&response_payload(SessionId("s-5".to_owned()), Ok(0)))
+            .unwrap();
+        assert_eq!(id, 5);
+        assert!(custom.is_empty());
+    }
+
+    #[test]
+    fn test_request_deadline() {
+        let client: RpcClient<Json, TestMethod, i64> = RpcClient::new();
+        let now = Instant::now();
+        let req = client.request(TestMethod::Test {}).unwrap();
+        assert!(!req.is_expired(now + Duration::from_secs(30)));
+        let req = req.with_deadline(now + Duration::from_secs(1));
+        assert_eq!(req.deadline(), Some(now + Duration::from_secs(1)));
+        assert!(!req.is_expired(now));
+        assert!(req.is_expired(now + Duration::from_secs(1)));
+        assert!(req.is_expired(now + Duration::from_secs(2)));
+    }
+
+    #[test]
+    fn test_request_with_id() {
+        let client: RpcClient<Json, TestMethod, i64> = RpcClient::new();
+        let req = client
+            .request_with_id(42, TestMethod::Add { a: 1, b: 2 })
+            .unwrap();
+        assert_eq!(req.id(), Some(42));
+        assert_eq!(
+            req.handle_response(&response_payload(Id::from(42), Ok(3)))
+                .unwrap(),
+            3
+        );
+        assert!(req
+            .handle_response(&response_payload(Id::from(0), Ok(3)))
+            .is_err());
+        // the counter is not touched
+        assert_eq!(client.request(TestMethod::Test {}).unwrap().id(), Some(0));
+    }
+
+    #[test]
+    fn test_retry() {
+        let client: RpcClient<Json, TestMethod, i64> = RpcClient::new();
+        let req = client.request(TestMethod::Add { a: 1, b: 2 }).unwrap();
+        let mut sent = 0;
+        let result = retry(&req, 5, |payload| {
+            assert_eq!(payload, req.payload());
+            sent += 1;
+            match sent {
+                1 => None,
+                2 => Some(b"{".to_vec()),
+                _ => Some(response_payload(Id::from(0), Ok(3))),
+            }
+        });
+        assert_eq!(result.unwrap(), 3);
+        assert_eq!(sent, 3);
+        // server errors are not retried
+        let mut sent = 0;
+        let result = retry(&req, 5, |_| {
+            sent += 1;
+            Some(response_payload(Id::from(0), Err(RpcError::INVALID_PARAMS)))
+        });
+        assert_eq!(result.unwrap_err(), RpcError::INVALID_PARAMS);
+        assert_eq!(sent, 1);
+        assert!(retry(&req, 2, |_| None).is_err());
+    }
+
+    #[test]
+    fn test_method_name() {
+        let client: RpcClient<Json, TestMethod, i64> = RpcClient::new();
+        let req = client.request(TestMethod::Add { a: 1, b: 2 }).unwrap();
+        assert_eq!(req.method_name(), Some("add"));
+        assert_eq!(req.method_name(), Some("add"));
+        let req = RpcClientRequest::<Json, TestMethod, i64>::new(Some(1), b"{".to_vec());
+        assert_eq!(req.method_name(), None);
+    }
+
+    #[test]
+    fn test_batch_duplicate_ids() {
+        let client: RpcClient<Json, TestMethod, i64> = RpcClient::new().with_id_generator(|| 1);
+        let batch = client
+            .batch([TestMethod::Test {}, TestMethod::Add { a: 1, b: 2 }])
+            .unwrap();
+        assert_eq!(batch.duplicate_ids(), vec![1]);
+        let responses = vec![
+            Response::from_handler_response(Id::from(1), HandlerResponse::Ok(0)),
+            Response::from_handler_response(Id::from(1), HandlerResponse::Ok(3)),
+        ];
+        let results: Vec<(BatchCallId, i64)> = batch
+            .into_results(&Json::pack(&responses).unwrap())
+            .map(|(id, result)| (id, result.unwrap()))
+            .collect();
+        assert_eq!(
+            results,
+            vec![
+                (BatchCallId::DuplicateId(1), 0),
+                (BatchCallId::DuplicateId(1), 3)
+            ]
+        );
+    }
+
+    #[test]
+    fn test_batch_collector() {
+        let client: RpcClient<Json, TestMethod, i64> = RpcClient::new();
+        let batch = client
+            .batch([
+                TestMethod::Test {},
+                TestMethod::Add { a: 1, b: 2 },
+                TestMethod::Add { a: 3, b: 4 },
+            ])
+            .unwrap();
+        let mut collector = batch.collector();
+        assert_eq!(collector.pending(), vec![0, 1, 2]);
+        assert_eq!(collector.push(&response_payload(Id::from(2), Ok(7))), Ok(2));
+        let error = response_payload(Id::from(0), Err(RpcError::INTERNAL_ERROR));
+        assert_eq!(collector.push(&error), Ok(0));
+        assert!(!collector.is_complete());
+        assert_eq!(collector.pending(), vec![1]);
+        // already answered and unknown calls
+        assert!(collector
+            .push(&response_payload(Id::from(2), Ok(7)))
+            .is_err());
+        assert!(collector
+            .push(&response_payload(Id::from(9), Ok(0)))
+            .is_err());
+        assert_eq!(collector.push(&response_payload(Id::from(1), Ok(3))), Ok(1));
+        assert!(collector.is_complete());
+        let results: Vec<(BatchCallId, RpcResult<i64>)> = collector.into_results().collect();
+        assert_eq!(
+            results,
+            vec![
+                (BatchCallId::Unique(0), Err(RpcError::INTERNAL_ERROR)),
+                (BatchCallId::Unique(1), Ok(3)),
+                (BatchCallId::Unique(2), Ok(7)),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_decode_result() {
+        assert_eq!(
+            decode_result::<Json, i64>(&response_payload(Id::from(1), Ok(3))),
+            Ok(3)
+        );
+        assert_eq!(
+            decode_result::<Json, i64>(&response_payload(
+                Id::from("x"),
+                Err(RpcError::method_not_found("test"))
+            ))
+            .unwrap_err()
+            .kind(),
+            RpcErrorKind::MethodNotFound
+        );
+        assert_eq!(
+            decode_result::<Json, i64>(b"{").unwrap_err().kind(),
+            RpcErrorKind::ParseError
+        );
+    }
+
+    #[test]
+    fn test_missing_result_and_error() {
+        let client: RpcClient<Json, TestMethod, i64> = RpcClient::new();
+        let req = client.request(TestMethod::Test {}).unwrap();
+        let payload = format!(
+            r#"{{"{}":"2.0","{}":0}}"#,
+            crate::field_names::JSONRPC,
+            crate::field_names::ID
+        );
+        match req.handle_response(payload.as_bytes()) {
+            Err(ClientError::Parse(message)) => assert_eq!(message, ERR_MISSING_RESULT),
+            res => panic!("unexpected result: {:?}", res),
+        }
+        assert_eq!(
+            decode_result::<Json, i64>(payload.as_bytes()),
+            Err(RpcError::new(
+                RpcErrorKind::ParseError,
+                ERR_MISSING_RESULT.to_owned()
+            ))
+        );
+    }
+
+    #[test]
+    fn test_id_generator_counter() {
+        let client: RpcClient<Json, TestMethod, i64> = RpcClient::new().with_id_generator(|| 7);
+        assert_eq!(client.request(TestMethod::Test {}).unwrap().id(), Some(7));
+        assert_eq!(
+            client.request_checked(TestMethod::Test {}).unwrap().id(),
+            Some(7)
+        );
+        // the counter is not used
+        assert_eq!(client.current_id(), 0);
+    }
 }
@@ -0,0 +1,144 @@
+#[cfg(not(feature = "std"))]
+use alloc::vec::Vec;
+
+use serde::{Deserialize, Serialize};
+
+use super::DataFormat;
+
+/// Bincode data format packer (for `no_std` mode), uses the standard bincode configuration.
+///
+/// Like postcard, bincode relies on the field order rather than names, so only the `no_std`
+/// (`i`/`m`/`p`) layout is supported, optional fields are always serialized and methods must be
+/// externally tagged enums. The `std` layout (flattened methods and `serde_json::Value` ids)
+/// can not be packed with bincode, in `std` mode all calls fail with
+/// [`StdLayoutError`](super::StdLayoutError).
+pub struct Packer;
+
+#[cfg(not(feature = "std"))]
+impl DataFormat for Packer {
+    type PackError = bincode::error::EncodeError;
+    type UnpackError = bincode::error::DecodeError;
+
+    fn pack<D: Serialize>(data: &D) -> Result<Vec<u8>, Self::PackError> {
+        bincode::serde::encode_to_vec(data, bincode::config::standard())
+    }
+
+    fn unpack<'de, T: Deserialize<'de>>(payload: &'de [u8]) -> Result<T, Self::UnpackError> {
+        bincode::serde::borrow_decode_from_slice(payload, bincode::config::standard())
+            .map(|(data, _)| data)
+    }
+}
+
+#[cfg(feature = "std")]
+impl DataFormat for Packer {
+    type PackError = super::StdLayoutError;
+    type UnpackError = super::StdLayoutError;
+
+    fn pack<D: Serialize>(_data: &D) -> Result<Vec<u8>, Self::PackError> {
+        Err(super::StdLayoutError)
+    }
+
+    fn unpack<'de, T: Deserialize<'de>>(_payload: &'de [u8]) -> Result<T, Self::UnpackError> {
+        Err(super::StdLayoutError)
+    }
+}
+
+#[cfg(all(test, not(feature = "std")))]
+mod tests {
+    use serde::{Deserialize, Serialize};
+
+    use super::Packer;
+    use crate::{
+        dataformat::DataFormat,
+        request::Request,
+        response::{HandlerResponse, Response},
+        RpcError,
+    };
+
+    #[derive(Serialize, Deserialize, Debug, PartialEq)]
+    enum TestMethod {
+        Set { key: u16, values: [u8; 3] },
+        Ping,
+    }
+
+    fn roundtrip(request: &Request<TestMethod>) -> Request<TestMethod> {
+        let unpacked: Request<TestMethod> =
+            Packer::unpack(&Packer::pack(request).unwrap()).unwrap();
+        assert_eq!(unpacked.has_version(), request.has_version());
+        unpacked
+    }
+
+    #[test]
+    fn test_request_roundtrip() {
+        let method = TestMethod::Set {
+            key: 300,
+            values: [1, 2, 3],
+        };
+        let (id, method) = roundtrip(&Request::new(u32::MAX, method)).into_parts();
+        assert_eq!(id, Some(u32::MAX));
+        assert_eq!(
+            method,
+            TestMethod::Set {
+                key: 300,
+                values: [1, 2, 3]
+            }
+        );
+        // absent ids are encoded as `None` rather than skipped
+        let (id, method) = roundtrip(&Request::new0(TestMethod::Ping)).into_parts();
+        assert_eq!(id, None);
+        assert_eq!(method, TestMethod::Ping);
+    }
+
+    #[test]
+    fn test_response_roundtrip() {
+        for response in [
+            Response::<u32>::from_handler_response(7, HandlerResponse::Ok(42)),
+            Response::<u32>::error(8, RpcError::INVALID_PARAMS),
+        ] {
+            let unpacked: Response<u32> =
+                Packer::unpack(&Packer::pack(&response).unwrap()).unwrap();
+            assert_eq!(unpacked.has_version(), response.has_version());
+            let (id, result) = unpacked.parts();
+            let (expected_id, expected) = response.parts();
+            assert_eq!(id, expected_id);
+            assert_eq!(result.ok(), expected.ok());
+            assert_eq!(result.err(), expected.err());
+        }
+    }
+
+    #[test]
+    fn test_varint_ids() {
+        // the standard configuration encodes integers as varints
+        let small = Packer::pack(&Request::new(7, TestMethod::Ping)).unwrap();
+        let large = Packer::pack(&Request::new(u32::MAX, TestMethod::Ping)).unwrap();
+        assert_eq!(large.len() - small.len(), 4);
+    }
+
+    #[test]
+    fn test_truncated_payload() {
+        let payload = Packer::pack(&Request::new(7, TestMethod::Ping)).unwrap();
+        assert!(matches!(
+            Packer::unpack::<Request<TestMethod>>(&payload[..payload.len() - 1]),
+            Err(bincode::error::DecodeError::UnexpectedEnd { .. })
+        ));
+    }
+}
+
+#[cfg(all(test, feature = "std"))]
+mod tests {
+    use super::Packer;
+    use crate::{
+        dataformat::{DataFormat, StdLayoutError},
+        request::Request,
+    };
+
+    #[test]
+    fn test_std_layout_rejected() {
+        let request = Request::<u32>::new0(1);
+        assert_eq!(Packer::pack(&request).unwrap_err(), StdLayoutError);
+        assert_eq!(
+            Packer::unpack::<Request<u32>>(&[0]).unwrap_err(),
+            StdLayoutError
+        );
+    }
+}
@@ -0,0 +1,59 @@
+use serde::{Deserialize, Serialize};
+
+use super::DataFormat;
+
+/// CBOR data format packer.
+///
+/// Note: the payload is decoded into an intermediate CBOR value, so method and result types
+/// with borrowed fields (e.g. `&str`) can not be unpacked, use owned types instead.
+pub struct Packer;
+
+impl DataFormat for Packer {
+    type PackError = ciborium::ser::Error<std::io::Error>;
+    type UnpackError = ciborium::de::Error<std::io::Error>;
+    const CONTENT_TYPE: &'static str = "application/cbor";
+
+    fn pack<D: Serialize>(data: &D) -> Result<Vec<u8>, Self::PackError> {
+        let mut buf = Vec::new();
+        ciborium::into_writer(data, &mut buf)?;
+        Ok(buf)
+    }
+
+    fn pack_into<D: Serialize>(data: &D, buf: &mut Vec<u8>) -> Result<(), Self::PackError> {
+        ciborium::into_writer(data, buf)
+    }
+
+    fn unpack<'de, T: Deserialize<'de>>(payload: &'de [u8]) -> Result<T, Self::UnpackError> {
+        unpack_value(payload)
+    }
+}
+
+#[cfg(feature = "compression")]
+impl super::UnpackCopied for Packer {
+    fn unpack_copied<'de, T: Deserialize<'de>>(payload: &[u8]) -> Result<T, Self::UnpackError> {
+        unpack_value(payload)
+    }
+}
+
+fn unpack_value<'de, T: Deserialize<'de>>(
+    payload: &[u8],
+) -> Result<T, ciborium::de::Error<std::io::Error>> {
+    ciborium::from_reader::<ciborium::Value, _>(payload)?
+        .deserialized()
+        .map_err(|e| ciborium::de::Error::Semantic(None, e.to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Packer;
+    use crate::dataformat::{
+        tests::{assert_test_request, test_request},
+        DataFormat,
+    };
+
+    #[test]
+    fn test_request_roundtrip() {
+        let payload = Packer::pack(&test_request()).unwrap();
+        assert_test_request(Packer::unpack(&payload).unwrap());
+    }
+}
@@ -0,0 +1,117 @@
+use core::{fmt, marker::PhantomData};
+use std::io::{Read, Write};
+
+use flate2::{read::GzDecoder, write::GzEncoder, Compression};
+use serde::{Deserialize, Serialize};
+
+use super::{DataFormat, UnpackCopied};
+
+/// The default limit of the decompressed payload size of [`Compressed`] (16 MiB)
+pub const DEFAULT_MAX_UNPACKED_LEN: usize = 16 * 1024 * 1024;
+
+/// Gzip-compressed data format, wraps another data format, e.g. `Compressed<Msgpack>`.
+///
+/// Payloads which are decompressed into more than `MAX_UNPACKED_LEN` bytes are rejected with
+/// [`CompressedError::TooLarge`] (protects against compression bombs).
+///
+/// Note: the payload is unpacked from a temporary decompression buffer, so method and result
+/// types with borrowed fields (e.g. `&str`) can not be unpacked, use owned types instead.
+pub struct Compressed<D, const MAX_UNPACKED_LEN: usize = DEFAULT_MAX_UNPACKED_LEN> {
+    _phantom: PhantomData<D>,
+}
+
+/// Compressed data format error
+#[allow(clippy::module_name_repetitions)]
+#[derive(Debug)]
+pub enum CompressedError<E> {
+    /// Inner data format error
+    Format(E),
+    /// Compression/decompression error
+    Io(std::io::Error),
+    /// The decompressed payload exceeds the size limit (contains the limit)
+    TooLarge(usize),
+}
+
+impl<E: fmt::Display> fmt::Display for CompressedError<E> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CompressedError::Format(e) => write!(f, "format error: {}", e),
+            CompressedError::Io(e) => write!(f, "compression error: {}", e),
+            CompressedError::TooLarge(max) => {
+                write!(f, "decompressed payload exceeds {} bytes", max)
+            }
+        }
+    }
+}
+
+impl<E: std::error::Error + 'static> std::error::Error for CompressedError<E> {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            CompressedError::Format(e) => Some(e),
+            CompressedError::Io(e) => Some(e),
+            CompressedError::TooLarge(_) => None,
+        }
+    }
+}
+
+impl<D: UnpackCopied, const MAX_UNPACKED_LEN: usize> DataFormat
+    for Compressed<D, MAX_UNPACKED_LEN>
+{
+    type PackError = CompressedError<D::PackError>;
+    type UnpackError = CompressedError<D::UnpackError>;
+    const CONTENT_TYPE: &'static str = "application/gzip";
+
+    fn pack<T: Serialize>(data: &T) -> Result<Vec<u8>, Self::PackError> {
+        let packed = D::pack(data).map_err(CompressedError::Format)?;
+        let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+        encoder.write_all(&packed).map_err(CompressedError::Io)?;
+        encoder.finish().map_err(CompressedError::Io)
+    }
+
+    fn unpack<'de, T: Deserialize<'de>>(payload: &'de [u8]) -> Result<T, Self::UnpackError> {
+        let mut buf = Vec::new();
+        // one extra byte is read to detect oversized payloads
+        let limit = u64::try_from(MAX_UNPACKED_LEN)
+            .unwrap_or(u64::MAX)
+            .saturating_add(1);
+        GzDecoder::new(payload)
+            .take(limit)
+            .read_to_end(&mut buf)
+            .map_err(CompressedError::Io)?;
+        if buf.len() > MAX_UNPACKED_LEN {
+            return Err(CompressedError::TooLarge(MAX_UNPACKED_LEN));
+        }
+        D::unpack_copied(&buf).map_err(CompressedError::Format)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dataformat::{
+        tests::{assert_test_request, test_request},
+        Json,
+    };
+
+    #[test]
+    fn test_request_roundtrip() {
+        let payload = Compressed::<Json>::pack(&test_request()).unwrap();
+        assert_test_request(Compressed::<Json>::unpack(&payload).unwrap());
+    }
+
+    #[test]
+    fn test_unpacked_len_limit() {
+        let payload = Compressed::<Json>::pack(&vec![0u8; 1024]).unwrap();
+        assert!(payload.len() < 64);
+        assert!(matches!(
+            Compressed::<Json, 1024>::unpack::<Vec<u8>>(&payload),
+            Err(CompressedError::TooLarge(1024))
+        ));
+        assert_eq!(
+            Compressed::<Json, 4096>::unpack::<Vec<u8>>(&payload)
+                .unwrap()
+                .len(),
+            1024
+        );
+    }
+}
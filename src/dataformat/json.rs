@@ -5,15 +5,89 @@ use super::DataFormat;
 /// JSON data format packer.
 pub struct Packer;
 
+#[cfg(feature = "compression")]
+impl super::UnpackCopied for Packer {
+    fn unpack_copied<'de, T: Deserialize<'de>>(payload: &[u8]) -> Result<T, Self::UnpackError> {
+        unpack_copied(payload)
+    }
+}
+
 impl DataFormat for Packer {
     type PackError = serde_json::Error;
     type UnpackError = serde_json::Error;
+    const IS_TEXT: bool = true;
+    const CONTENT_TYPE: &'static str = "application/json";
 
     fn pack<D: Serialize>(data: &D) -> Result<Vec<u8>, Self::PackError> {
         serde_json::to_vec(data)
     }
 
+    fn pack_into<D: Serialize>(data: &D, buf: &mut Vec<u8>) -> Result<(), Self::PackError> {
+        serde_json::to_writer(buf, data)
+    }
+
+    fn unpack<'de, T: Deserialize<'de>>(payload: &'de [u8]) -> Result<T, Self::UnpackError> {
+        serde_json::from_slice(payload)
+    }
+}
+
+/// JSON data format packer with indented (human-readable) output, e.g. for debugging.
+pub struct PrettyPacker;
+
+#[cfg(feature = "compression")]
+impl super::UnpackCopied for PrettyPacker {
+    fn unpack_copied<'de, T: Deserialize<'de>>(payload: &[u8]) -> Result<T, Self::UnpackError> {
+        unpack_copied(payload)
+    }
+}
+
+impl DataFormat for PrettyPacker {
+    type PackError = serde_json::Error;
+    type UnpackError = serde_json::Error;
+    const IS_TEXT: bool = true;
+    const CONTENT_TYPE: &'static str = "application/json";
+
+    fn pack<D: Serialize>(data: &D) -> Result<Vec<u8>, Self::PackError> {
+        serde_json::to_vec_pretty(data)
+    }
+
+    fn pack_into<D: Serialize>(data: &D, buf: &mut Vec<u8>) -> Result<(), Self::PackError> {
+        serde_json::to_writer_pretty(buf, data)
+    }
+
     fn unpack<'de, T: Deserialize<'de>>(payload: &'de [u8]) -> Result<T, Self::UnpackError> {
         serde_json::from_slice(payload)
     }
 }
+
+#[cfg(feature = "compression")]
+fn unpack_copied<'de, T: Deserialize<'de>>(payload: &[u8]) -> Result<T, serde_json::Error> {
+    // the reader-based deserializer copies strings instead of borrowing them
+    let mut deserializer = serde_json::Deserializer::from_reader(payload);
+    let value = T::deserialize(&mut deserializer)?;
+    deserializer.end()?;
+    Ok(value)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Packer, PrettyPacker};
+    use crate::dataformat::{
+        tests::{assert_test_request, test_request},
+        DataFormat,
+    };
+
+    #[test]
+    fn test_pretty_roundtrip() {
+        let payload = PrettyPacker::pack(&test_request()).unwrap();
+        assert!(payload.contains(&b'\n'));
+        assert_test_request(PrettyPacker::unpack(&payload).unwrap());
+        // the pretty output is parsed the same way as the compact one
+        let compact = Packer::pack(&test_request()).unwrap();
+        let value: serde_json::Value = Packer::unpack(&payload).unwrap();
+        assert_eq!(
+            value,
+            Packer::unpack::<serde_json::Value>(&compact).unwrap()
+        );
+    }
+}
@@ -1,23 +1,141 @@
+#[cfg(any(not(feature = "std"), feature = "postcard", feature = "bincode"))]
 use core::fmt;
 
+#[cfg(not(feature = "std"))]
+use alloc::vec::Vec;
+
 use serde::{Deserialize, Serialize};
 
+#[cfg(feature = "std")]
 mod json;
-pub use json::Packer as Json;
-#[cfg(feature = "msgpack")]
+#[cfg(feature = "std")]
+pub use json::{Packer as Json, PrettyPacker as JsonPretty};
+#[cfg(all(feature = "std", feature = "msgpack"))]
 mod msgpack;
-#[cfg(feature = "msgpack")]
-pub use msgpack::Packer as Msgpack;
+#[cfg(all(feature = "std", feature = "msgpack"))]
+pub use msgpack::{CompactPacker as MsgpackCompact, Packer as Msgpack};
+#[cfg(all(feature = "std", feature = "cbor"))]
+mod cbor;
+#[cfg(all(feature = "std", feature = "cbor"))]
+pub use cbor::Packer as Cbor;
+#[cfg(feature = "compression")]
+mod compressed;
+#[cfg(feature = "compression")]
+pub use compressed::{Compressed, CompressedError, DEFAULT_MAX_UNPACKED_LEN};
+#[cfg(feature = "postcard")]
+mod postcard;
+#[cfg(feature = "postcard")]
+pub use self::postcard::Packer as Postcard;
+#[cfg(feature = "bincode")]
+mod bincode;
+#[cfg(feature = "bincode")]
+pub use self::bincode::Packer as Bincode;
+
+#[cfg(feature = "std")]
+/// Data format error bound (`std::error::Error + Send + Sync` in `std` mode, `Display + Debug` in
+/// `no_std` mode), implemented automatically.
+pub trait FormatError: std::error::Error + Send + Sync + 'static {}
+#[cfg(feature = "std")]
+impl<T> FormatError for T where T: std::error::Error + Send + Sync + 'static {}
+#[cfg(not(feature = "std"))]
+/// Data format error bound (`std::error::Error + Send + Sync` in `std` mode, `Display + Debug` in
+/// `no_std` mode), implemented automatically.
+pub trait FormatError: fmt::Display + fmt::Debug {}
+#[cfg(not(feature = "std"))]
+impl<T> FormatError for T where T: fmt::Display + fmt::Debug {}
 
 /// A trait for data formats that can be packed and unpacked.
 pub trait DataFormat {
     /// The error type for packing.
-    type PackError: fmt::Display;
+    type PackError: FormatError;
     /// The error type for unpacking.
-    type UnpackError: fmt::Display;
+    type UnpackError: FormatError;
+    /// Is the packed data a valid UTF-8 text (e.g. to choose a transport frame type).
+    const IS_TEXT: bool = false;
+    /// MIME type of the packed data (e.g. for HTTP `Content-Type` headers).
+    const CONTENT_TYPE: &'static str = "application/octet-stream";
 
     /// Pack data into a byte vector.
     fn pack<D: Serialize>(data: &D) -> Result<Vec<u8>, Self::PackError>;
+    /// Pack data, appending it to a caller-provided buffer (allows to reuse the buffer).
+    fn pack_into<D: Serialize>(data: &D, buf: &mut Vec<u8>) -> Result<(), Self::PackError> {
+        buf.extend(Self::pack(data)?);
+        Ok(())
+    }
     /// Unpack data from a byte slice.
     fn unpack<'de, T: Deserialize<'de>>(payload: &'de [u8]) -> Result<T, Self::UnpackError>;
 }
+
+#[cfg(all(feature = "std", any(feature = "postcard", feature = "bincode")))]
+/// Error of the positional data formats (`Postcard`, `Bincode`) in `std` mode: the `std`
+/// layout (flattened methods and `serde_json::Value` ids) can not be packed or unpacked with
+/// them, so all calls fail. Use the positional formats in `no_std` mode.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct StdLayoutError;
+
+#[cfg(all(feature = "std", any(feature = "postcard", feature = "bincode")))]
+impl fmt::Display for StdLayoutError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "positional data formats do not support the std layout, use them in no_std mode"
+        )
+    }
+}
+
+#[cfg(all(feature = "std", any(feature = "postcard", feature = "bincode")))]
+impl std::error::Error for StdLayoutError {}
+
+#[cfg(feature = "compression")]
+/// Data formats which can unpack data from a short-living buffer (e.g. a decompressed one), used
+/// by format adapters such as [`Compressed`].
+pub trait UnpackCopied: DataFormat {
+    /// Unpack data from a byte slice, the result never borrows from the slice (borrowed fields,
+    /// e.g. `&str`, can not be unpacked).
+    fn unpack_copied<'de, T: Deserialize<'de>>(payload: &[u8]) -> Result<T, Self::UnpackError>;
+}
+
+#[cfg(all(test, feature = "std"))]
+pub(crate) mod tests {
+    use serde::{Deserialize, Serialize};
+
+    use super::{DataFormat, Json};
+    use crate::request::Request;
+
+    #[derive(Serialize, Deserialize, Debug, PartialEq)]
+    #[cfg_attr(
+        feature = "canonical",
+        serde(tag = "method", content = "params", deny_unknown_fields)
+    )]
+    #[cfg_attr(
+        not(feature = "canonical"),
+        serde(tag = "m", content = "p", deny_unknown_fields)
+    )]
+    pub(crate) enum TestMethod {
+        #[serde(rename = "hello")]
+        Hello { name: String, tags: Vec<String> },
+    }
+
+    pub(crate) fn test_request() -> Request<TestMethod> {
+        Request::new(
+            7.into(),
+            TestMethod::Hello {
+                name: "world".to_owned(),
+                tags: vec!["a".to_owned(), "b".to_owned()],
+            },
+        )
+    }
+
+    pub(crate) fn assert_test_request(request: Request<TestMethod>) {
+        let (id, method) = request.into_parts();
+        assert_eq!(id, Some(7.into()));
+        let (_, expected) = test_request().into_parts();
+        assert_eq!(method, expected);
+    }
+
+    #[test]
+    fn test_json_request_roundtrip() {
+        let payload = Json::pack(&test_request()).unwrap();
+        assert_test_request(Json::unpack(&payload).unwrap());
+    }
+}
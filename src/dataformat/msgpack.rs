@@ -5,15 +5,127 @@ use super::DataFormat;
 /// MessagePack data format packer.
 pub struct Packer;
 
+#[cfg(feature = "compression")]
+impl super::UnpackCopied for Packer {
+    fn unpack_copied<'de, T: Deserialize<'de>>(payload: &[u8]) -> Result<T, Self::UnpackError> {
+        T::deserialize(&mut rmp_serde::Deserializer::new(payload))
+    }
+}
+
 impl DataFormat for Packer {
     type PackError = rmp_serde::encode::Error;
     type UnpackError = rmp_serde::decode::Error;
+    const CONTENT_TYPE: &'static str = "application/msgpack";
 
     fn pack<D: Serialize>(data: &D) -> Result<Vec<u8>, Self::PackError> {
         rmp_serde::to_vec_named(data)
     }
 
+    fn pack_into<D: Serialize>(data: &D, buf: &mut Vec<u8>) -> Result<(), Self::PackError> {
+        rmp_serde::encode::write_named(buf, data)
+    }
+
     fn unpack<'de, T: Deserialize<'de>>(payload: &'de [u8]) -> Result<T, Self::UnpackError> {
         rmp_serde::from_slice(payload)
     }
 }
+
+/// Compact MessagePack data format packer, structs (e.g. method params and results) are packed as
+/// arrays with no field names. Produces smaller payloads than [`Packer`] but requires the field
+/// order to be the same on both sides. The request/response envelope is still packed as a map, so
+/// the compact `i`/`m`/`p` layout (the non-canonical mode) is recommended. Methods must be unit or
+/// newtype variants (e.g. `Hello(HelloParams)`), struct variants can not be unpacked.
+pub struct CompactPacker;
+
+#[cfg(feature = "compression")]
+impl super::UnpackCopied for CompactPacker {
+    fn unpack_copied<'de, T: Deserialize<'de>>(payload: &[u8]) -> Result<T, Self::UnpackError> {
+        T::deserialize(&mut rmp_serde::Deserializer::new(payload))
+    }
+}
+
+impl DataFormat for CompactPacker {
+    type PackError = rmp_serde::encode::Error;
+    type UnpackError = rmp_serde::decode::Error;
+    const CONTENT_TYPE: &'static str = "application/msgpack";
+
+    fn pack<D: Serialize>(data: &D) -> Result<Vec<u8>, Self::PackError> {
+        rmp_serde::to_vec(data)
+    }
+
+    fn pack_into<D: Serialize>(data: &D, buf: &mut Vec<u8>) -> Result<(), Self::PackError> {
+        rmp_serde::encode::write(buf, data)
+    }
+
+    fn unpack<'de, T: Deserialize<'de>>(payload: &'de [u8]) -> Result<T, Self::UnpackError> {
+        rmp_serde::from_slice(payload)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde::{Deserialize, Serialize};
+
+    use super::{CompactPacker, Packer};
+    use crate::{
+        dataformat::{
+            tests::{assert_test_request, test_request},
+            DataFormat,
+        },
+        request::Request,
+    };
+
+    #[derive(Serialize, Deserialize, Debug, PartialEq)]
+    struct HelloParams {
+        name: String,
+        tags: Vec<String>,
+    }
+
+    #[derive(Serialize, Deserialize, Debug, PartialEq)]
+    #[cfg_attr(feature = "canonical", serde(tag = "method", content = "params"))]
+    #[cfg_attr(not(feature = "canonical"), serde(tag = "m", content = "p"))]
+    enum TestMethod {
+        #[serde(rename = "hello")]
+        Hello(HelloParams),
+    }
+
+    fn hello_request() -> Request<TestMethod> {
+        Request::new(7.into(), hello())
+    }
+
+    fn hello() -> TestMethod {
+        TestMethod::Hello(HelloParams {
+            name: "world".to_owned(),
+            tags: vec!["a".to_owned(), "b".to_owned()],
+        })
+    }
+
+    #[test]
+    fn test_request_roundtrip() {
+        let payload = Packer::pack(&test_request()).unwrap();
+        assert_test_request(Packer::unpack(&payload).unwrap());
+    }
+
+    #[test]
+    fn test_compact_request_roundtrip() {
+        let payload = CompactPacker::pack(&hello_request()).unwrap();
+        let (id, method) = CompactPacker::unpack::<Request<TestMethod>>(&payload)
+            .unwrap()
+            .into_parts();
+        assert_eq!(id, Some(7.into()));
+        assert_eq!(method, hello());
+    }
+
+    #[test]
+    fn test_compact_size() {
+        let request = hello_request();
+        let named = Packer::pack(&request).unwrap();
+        let compact = CompactPacker::pack(&request).unwrap();
+        assert!(
+            compact.len() < named.len(),
+            "compact: {}, named: {}",
+            compact.len(),
+            named.len()
+        );
+    }
+}
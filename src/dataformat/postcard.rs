@@ -0,0 +1,97 @@
+#[cfg(not(feature = "std"))]
+use alloc::vec::Vec;
+
+use serde::{Deserialize, Serialize};
+
+use super::DataFormat;
+
+/// Postcard data format packer (for `no_std` mode).
+///
+/// Postcard relies on the field order rather than names, so the `no_std` (`i`/`m`/`p`) layout is
+/// used, optional fields are always serialized. Methods must be externally tagged enums (the
+/// serde default), as postcard does not support tagged enum representations. The `std` layout
+/// (flattened methods and `serde_json::Value` ids) can not be packed with postcard, in `std`
+/// mode all calls fail with [`StdLayoutError`](super::StdLayoutError).
+pub struct Packer;
+
+#[cfg(not(feature = "std"))]
+impl DataFormat for Packer {
+    type PackError = postcard::Error;
+    type UnpackError = postcard::Error;
+
+    fn pack<D: Serialize>(data: &D) -> Result<Vec<u8>, Self::PackError> {
+        postcard::to_allocvec(data)
+    }
+
+    fn unpack<'de, T: Deserialize<'de>>(payload: &'de [u8]) -> Result<T, Self::UnpackError> {
+        postcard::from_bytes(payload)
+    }
+}
+
+#[cfg(feature = "std")]
+impl DataFormat for Packer {
+    type PackError = super::StdLayoutError;
+    type UnpackError = super::StdLayoutError;
+
+    fn pack<D: Serialize>(_data: &D) -> Result<Vec<u8>, Self::PackError> {
+        Err(super::StdLayoutError)
+    }
+
+    fn unpack<'de, T: Deserialize<'de>>(_payload: &'de [u8]) -> Result<T, Self::UnpackError> {
+        Err(super::StdLayoutError)
+    }
+}
+
+#[cfg(all(test, not(feature = "std")))]
+mod tests {
+    use serde::{Deserialize, Serialize};
+
+    use super::Packer;
+    use crate::{
+        dataformat::DataFormat,
+        request::Request,
+        response::{HandlerResponse, Response},
+        RpcError,
+    };
+
+    #[derive(Serialize, Deserialize, Debug, PartialEq)]
+    enum TestMethod {
+        Hello { value: u32 },
+        Test,
+    }
+
+    #[test]
+    fn test_request_roundtrip() {
+        let request = Request::<TestMethod>::new(7, TestMethod::Hello { value: 42 });
+        let payload = Packer::pack(&request).unwrap();
+        let unpacked = Packer::unpack::<Request<TestMethod>>(&payload).unwrap();
+        assert_eq!(unpacked.has_version(), request.has_version());
+        let (id, method) = unpacked.into_parts();
+        assert_eq!(id, Some(7));
+        assert_eq!(method, TestMethod::Hello { value: 42 });
+        let payload = Packer::pack(&Request::<TestMethod>::new0(TestMethod::Test)).unwrap();
+        let (id, method) = Packer::unpack::<Request<TestMethod>>(&payload)
+            .unwrap()
+            .into_parts();
+        assert_eq!(id, None);
+        assert_eq!(method, TestMethod::Test);
+    }
+
+    #[test]
+    fn test_response_roundtrip() {
+        let response = Response::<u32>::from_handler_response(7, HandlerResponse::Ok(42));
+        let payload = Packer::pack(&response).unwrap();
+        let unpacked = Packer::unpack::<Response<u32>>(&payload).unwrap();
+        assert_eq!(unpacked.has_version(), response.has_version());
+        let (id, result) = unpacked.into_parts();
+        assert_eq!(id, 7);
+        assert_eq!(result.ok(), Some(&42));
+        let response = Response::<u32>::error(8, RpcError::INVALID_PARAMS);
+        let payload = Packer::pack(&response).unwrap();
+        let (id, result) = Packer::unpack::<Response<u32>>(&payload)
+            .unwrap()
+            .into_parts();
+        assert_eq!(id, 8);
+        assert_eq!(result.err(), Some(&RpcError::INVALID_PARAMS));
+    }
+}
@@ -2,7 +2,10 @@
 #![cfg_attr(not(feature = "std"), no_std)]
 #![deny(missing_docs)]
 
-use serde::{Deserialize, Serialize};
+#[cfg(all(any(feature = "postcard", feature = "bincode"), not(feature = "std")))]
+extern crate alloc;
+
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
 
 const JSONRPC_VERSION: &str = "2.0";
 
@@ -17,9 +20,66 @@ const ERR_INVALID_PROTOCOL_VERSION: &str = "Invalid protocol version";
 #[cfg(feature = "std")]
 /// RPC call id (`u32` in `no_std` mode, `serde_json::Value` in `std` mode)
 pub type Id = serde_json::Value;
-#[cfg(not(feature = "std"))]
+#[cfg(all(not(feature = "std"), not(feature = "string-id")))]
 /// RPC call id (`u32` in `no_std` mode, `serde_json::Value` in `std` mode)
 pub type Id = u32;
+#[cfg(all(not(feature = "std"), feature = "string-id"))]
+/// RPC call id (`heapless::String<64>` in `no_std` mode with the `string-id` feature)
+pub type Id = heapless::String<64>;
+
+/// RPC call id trait, allows to use custom id types (the default is [`Id`])
+pub trait RpcId: Serialize + DeserializeOwned + PartialEq + Clone + core::fmt::Debug {
+    /// Create an id from a numeric counter value (used by clients to generate ids)
+    fn from_u32(value: u32) -> Self;
+    /// Get the id as `u32` if it holds an integer which fits (`None` for other ids by default)
+    fn as_u32(&self) -> Option<u32> {
+        None
+    }
+}
+
+impl RpcId for u32 {
+    fn from_u32(value: u32) -> Self {
+        value
+    }
+    fn as_u32(&self) -> Option<u32> {
+        Some(*self)
+    }
+}
+
+impl RpcId for u64 {
+    fn from_u32(value: u32) -> Self {
+        value.into()
+    }
+    fn as_u32(&self) -> Option<u32> {
+        u32::try_from(*self).ok()
+    }
+}
+
+impl<const N: usize> RpcId for heapless::String<N> {
+    fn from_u32(value: u32) -> Self {
+        let mut id = heapless::String::new();
+        // a u32 takes up to 10 bytes, the id is truncated if N is smaller
+        let _ = core::fmt::Write::write_fmt(&mut id, format_args!("{}", value));
+        id
+    }
+}
+
+#[cfg(feature = "std")]
+impl RpcId for serde_json::Value {
+    fn from_u32(value: u32) -> Self {
+        value.into()
+    }
+    fn as_u32(&self) -> Option<u32> {
+        u32::try_from(self.as_u64()?).ok()
+    }
+}
+
+#[cfg(feature = "std")]
+impl RpcId for std::string::String {
+    fn from_u32(value: u32) -> Self {
+        value.to_string()
+    }
+}
 
 #[cfg(feature = "std")]
 type String = std::string::String;
@@ -29,7 +89,7 @@ type String = heapless::String<128>;
 #[cfg(feature = "std")]
 /// RPC client
 pub mod client;
-#[cfg(feature = "std")]
+#[cfg(any(feature = "std", feature = "postcard", feature = "bincode"))]
 /// Data serialization formats
 pub mod dataformat;
 /// RPC request
@@ -42,34 +102,152 @@ pub mod server;
 /// Miscellaneous tools
 pub mod tools;
 
+/// Wire field names of request and response objects in the active layout (canonical
+/// `id`/`method`/`params`/`result`/`error` or compact `i`/`m`/`p`/`r`/`e`), e.g. for custom
+/// serializers
+pub mod field_names {
+    /// Protocol version field (canonical mode only)
+    pub const JSONRPC: &str = "jsonrpc";
+    #[cfg(feature = "canonical")]
+    /// Call id field
+    pub const ID: &str = "id";
+    #[cfg(not(feature = "canonical"))]
+    /// Call id field
+    pub const ID: &str = "i";
+    #[cfg(feature = "canonical")]
+    /// Request method field
+    pub const METHOD: &str = "method";
+    #[cfg(not(feature = "canonical"))]
+    /// Request method field
+    pub const METHOD: &str = "m";
+    #[cfg(feature = "canonical")]
+    /// Request params field
+    pub const PARAMS: &str = "params";
+    #[cfg(not(feature = "canonical"))]
+    /// Request params field
+    pub const PARAMS: &str = "p";
+    #[cfg(feature = "canonical")]
+    /// Response result field
+    pub const RESULT: &str = "result";
+    #[cfg(not(feature = "canonical"))]
+    /// Response result field
+    pub const RESULT: &str = "r";
+    #[cfg(feature = "canonical")]
+    /// Response error field
+    pub const ERROR: &str = "error";
+    #[cfg(not(feature = "canonical"))]
+    /// Response error field
+    pub const ERROR: &str = "e";
+}
+
 fn de_validate_version<'de, D>(deserializer: D) -> Result<Option<()>, D::Error>
 where
     D: serde::Deserializer<'de>,
 {
-    let version: Option<&str> = Deserialize::deserialize(deserializer)?;
-    #[cfg(feature = "canonical")]
-    if version.map_or(false, |v| v != JSONRPC_VERSION) {
-        return Err(serde::de::Error::custom(ERR_INVALID_PROTOCOL_VERSION));
-    }
+    let version: Option<Version> = Deserialize::deserialize(deserializer)?;
     Ok(version.map(|_| ()))
 }
 
-#[allow(clippy::trivially_copy_pass_by_ref)]
-fn serialize_version<S>(_: &Option<()>, serializer: S) -> Result<S::Ok, S::Error>
+/// Protocol version header, validated in the canonical mode. Does not borrow the string, so can
+/// be de-serialized from owned values (e.g. `serde_json::Value`)
+struct Version;
+
+impl<'de> Deserialize<'de> for Version {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: serde::Deserializer<'de>,
+    {
+        deserializer.deserialize_str(VersionVisitor)
+    }
+}
+
+struct VersionVisitor;
+
+impl serde::de::Visitor<'_> for VersionVisitor {
+    type Value = Version;
+
+    fn expecting(&self, formatter: &mut core::fmt::Formatter) -> core::fmt::Result {
+        formatter.write_str("a protocol version string")
+    }
+    #[allow(unused_variables)]
+    fn visit_str<E: serde::de::Error>(self, v: &str) -> Result<Self::Value, E> {
+        #[cfg(feature = "canonical")]
+        if v != JSONRPC_VERSION {
+            return Err(E::custom(ERR_INVALID_PROTOCOL_VERSION));
+        }
+        Ok(Version)
+    }
+}
+
+#[cfg(feature = "null-id")]
+/// De-serializes an explicitly null id as `Some` (if the id type can hold null, e.g.
+/// `serde_json::Value`), an absent id is `None` (with `serde(default)`)
+fn de_nullable_id<'de, D, I>(deserializer: D) -> Result<Option<I>, D::Error>
+where
+    D: serde::Deserializer<'de>,
+    I: Deserialize<'de>,
+{
+    deserializer.deserialize_option(NullableIdVisitor(core::marker::PhantomData))
+}
+
+#[cfg(feature = "null-id")]
+struct NullableIdVisitor<I>(core::marker::PhantomData<I>);
+
+#[cfg(feature = "null-id")]
+impl<'de, I: Deserialize<'de>> serde::de::Visitor<'de> for NullableIdVisitor<I> {
+    type Value = Option<I>;
+
+    fn expecting(&self, formatter: &mut core::fmt::Formatter) -> core::fmt::Result {
+        formatter.write_str("a request id or null")
+    }
+    fn visit_none<E: serde::de::Error>(self) -> Result<Self::Value, E> {
+        // id types which can not hold null treat it as absent
+        Ok(I::deserialize(serde::de::value::UnitDeserializer::<E>::new()).ok())
+    }
+    fn visit_unit<E: serde::de::Error>(self) -> Result<Self::Value, E> {
+        self.visit_none()
+    }
+    fn visit_some<D: serde::Deserializer<'de>>(
+        self,
+        deserializer: D,
+    ) -> Result<Self::Value, D::Error> {
+        I::deserialize(deserializer).map(Some)
+    }
+}
+
+// serde `serialize_with` passes a reference to the field
+#[allow(clippy::trivially_copy_pass_by_ref, clippy::ref_option)]
+fn serialize_version<S>(version: &Option<()>, serializer: S) -> Result<S::Ok, S::Error>
 where
     S: serde::Serializer,
 {
-    JSONRPC_VERSION.serialize(serializer)
+    // positional formats serialize absent versions as well (see `SKIP_NONE`)
+    version.map(|()| JSONRPC_VERSION).serialize(serializer)
 }
 
-const RPC_ERROR_PARSE_ERROR: i16 = -32700;
-const RPC_ERROR_INVALID_REQUEST: i16 = -32600;
-const RPC_ERROR_METHOD_NOT_FOUND: i16 = -32601;
-const RPC_ERROR_INVALID_PARAMS: i16 = -32602;
-const RPC_ERROR_INTERNAL_ERROR: i16 = -32603;
+// positional formats (postcard and bincode in no_std mode) can not skip fields
+const SKIP_NONE: bool = !cfg!(all(
+    any(feature = "postcard", feature = "bincode"),
+    not(feature = "std")
+));
 
-/// RPC error kind
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+// serde `skip_serializing_if` passes a reference to the field
+#[allow(clippy::ref_option)]
+fn skip_none<T>(value: &Option<T>) -> bool {
+    SKIP_NONE && value.is_none()
+}
+
+const RPC_ERROR_PARSE_ERROR: i32 = -32700;
+const RPC_ERROR_INVALID_REQUEST: i32 = -32600;
+const RPC_ERROR_METHOD_NOT_FOUND: i32 = -32601;
+const RPC_ERROR_INVALID_PARAMS: i32 = -32602;
+const RPC_ERROR_INTERNAL_ERROR: i32 = -32603;
+const RPC_ERROR_SERVER_ERROR_MIN: i32 = -32099;
+const RPC_ERROR_SERVER_ERROR_MAX: i32 = -32000;
+
+/// RPC error kind. Kinds are compared by codes, so e.g. `Custom(-32050)` is equal to
+/// `ServerError(-32050)`, which it is parsed back as
+#[derive(Debug, Clone, Copy)]
 pub enum RpcErrorKind {
     /// Parse error
     ParseError,
@@ -81,31 +259,52 @@ pub enum RpcErrorKind {
     InvalidParams,
     /// Internal error
     InternalError,
-    /// Custom error
-    Custom(i16),
+    /// Implementation-defined server error (codes from -32099 to -32000)
+    ServerError(i32),
+    /// Custom error (codes from the server error range are parsed as
+    /// [`RpcErrorKind::ServerError`])
+    Custom(i32),
+}
+
+impl RpcErrorKind {
+    /// Returns `true` if the error code is in the implementation-defined server error range
+    pub fn is_server_error(&self) -> bool {
+        (RPC_ERROR_SERVER_ERROR_MIN..=RPC_ERROR_SERVER_ERROR_MAX).contains(&i32::from(*self))
+    }
 }
 
 #[cfg(feature = "std")]
 impl core::fmt::Display for RpcErrorKind {
     fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
-        write!(f, "{}", i16::from(*self))
+        write!(f, "{}", i32::from(*self))
     }
 }
 
-impl From<i16> for RpcErrorKind {
-    fn from(code: i16) -> Self {
+impl PartialEq for RpcErrorKind {
+    fn eq(&self, other: &Self) -> bool {
+        i32::from(*self) == i32::from(*other)
+    }
+}
+
+impl Eq for RpcErrorKind {}
+
+impl From<i32> for RpcErrorKind {
+    fn from(code: i32) -> Self {
         match code {
             RPC_ERROR_PARSE_ERROR => RpcErrorKind::ParseError,
             RPC_ERROR_INVALID_REQUEST => RpcErrorKind::InvalidRequest,
             RPC_ERROR_METHOD_NOT_FOUND => RpcErrorKind::MethodNotFound,
             RPC_ERROR_INVALID_PARAMS => RpcErrorKind::InvalidParams,
             RPC_ERROR_INTERNAL_ERROR => RpcErrorKind::InternalError,
+            RPC_ERROR_SERVER_ERROR_MIN..=RPC_ERROR_SERVER_ERROR_MAX => {
+                RpcErrorKind::ServerError(code)
+            }
             _ => RpcErrorKind::Custom(code),
         }
     }
 }
 
-impl From<RpcErrorKind> for i16 {
+impl From<RpcErrorKind> for i32 {
     fn from(code: RpcErrorKind) -> Self {
         match code {
             RpcErrorKind::ParseError => RPC_ERROR_PARSE_ERROR,
@@ -113,7 +312,7 @@ impl From<RpcErrorKind> for i16 {
             RpcErrorKind::MethodNotFound => RPC_ERROR_METHOD_NOT_FOUND,
             RpcErrorKind::InvalidParams => RPC_ERROR_INVALID_PARAMS,
             RpcErrorKind::InternalError => RPC_ERROR_INTERNAL_ERROR,
-            RpcErrorKind::Custom(code) => code,
+            RpcErrorKind::ServerError(code) | RpcErrorKind::Custom(code) => code,
         }
     }
 }
@@ -123,7 +322,7 @@ impl Serialize for RpcErrorKind {
     where
         S: serde::Serializer,
     {
-        i16::from(*self).serialize(serializer)
+        i32::from(*self).serialize(serializer)
     }
 }
 
@@ -132,33 +331,107 @@ impl<'de> Deserialize<'de> for RpcErrorKind {
     where
         D: serde::Deserializer<'de>,
     {
-        i16::deserialize(deserializer).map(RpcErrorKind::from)
+        i32::deserialize(deserializer).map(RpcErrorKind::from)
     }
 }
 
 /// RPC error type
-#[derive(Serialize, Deserialize, Debug)]
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
 pub struct RpcError {
     #[serde(rename = "code")]
     kind: RpcErrorKind,
-    #[serde(skip_serializing_if = "Option::is_none")]
+    #[serde(default, skip_serializing_if = "skip_none")]
     message: Option<String>,
+    #[cfg(feature = "std")]
+    #[serde(default, skip_serializing_if = "skip_none")]
+    data: Option<serde_json::Value>,
 }
 
 impl RpcError {
+    /// Parse error with no message (no allocations, cheap to clone)
+    pub const PARSE_ERROR: RpcError = RpcError::const_new0(RpcErrorKind::ParseError);
+    /// Invalid request error with no message (no allocations, cheap to clone)
+    pub const INVALID_REQUEST: RpcError = RpcError::const_new0(RpcErrorKind::InvalidRequest);
+    /// Method not found error with no message (no allocations, cheap to clone)
+    pub const METHOD_NOT_FOUND: RpcError = RpcError::const_new0(RpcErrorKind::MethodNotFound);
+    /// Invalid params error with no message (no allocations, cheap to clone)
+    pub const INVALID_PARAMS: RpcError = RpcError::const_new0(RpcErrorKind::InvalidParams);
+    /// Internal error with no message (no allocations, cheap to clone)
+    pub const INTERNAL_ERROR: RpcError = RpcError::const_new0(RpcErrorKind::InternalError);
+
     /// Create a new error
     pub fn new0(kind: RpcErrorKind) -> Self {
+        Self::from_parts(kind, None)
+    }
+    const fn const_new0(kind: RpcErrorKind) -> Self {
         Self {
             kind,
             message: None,
+            #[cfg(feature = "std")]
+            data: None,
         }
     }
     /// Create a new error with a message. The message must be `String` to have compatibility with
     /// `no_std` mode.
     pub fn new(kind: RpcErrorKind, message: String) -> Self {
+        Self::from_parts(kind, Some(message))
+    }
+    /// Create a new error with a message, fails if the message does not fit the string capacity
+    /// (128 bytes in `no_std` mode)
+    pub fn try_new(kind: RpcErrorKind, message: &str) -> Result<Self, CapacityError> {
+        Ok(Self::new(kind, try_string(message)?))
+    }
+    /// Create a new error with a message. In `no_std` mode the message is truncated to fit the
+    /// string capacity (128 bytes)
+    pub fn new_truncating(kind: RpcErrorKind, message: &str) -> Self {
+        Self::new_fmt(kind, format_args!("{}", message))
+    }
+    /// Create a new error with a message rendered from the displayable value (e.g. a data format
+    /// error). In `no_std` mode the message is written directly into the fixed-capacity string
+    /// (no allocator required) and truncated to fit it (128 bytes)
+    pub fn from_display<T: core::fmt::Display + ?Sized>(kind: RpcErrorKind, value: &T) -> Self {
+        Self::new_fmt(kind, format_args!("{}", value))
+    }
+    /// Create a new `MethodNotFound` error with a standard message for the method name
+    pub fn method_not_found(name: &str) -> Self {
+        Self::new_fmt(
+            RpcErrorKind::MethodNotFound,
+            format_args!("method '{}' not found", name),
+        )
+    }
+    /// Create a new `InvalidParams` error with a standard message for the details
+    pub fn invalid_params(detail: &str) -> Self {
+        Self::new_fmt(
+            RpcErrorKind::InvalidParams,
+            format_args!("invalid params: {}", detail),
+        )
+    }
+    fn new_fmt(kind: RpcErrorKind, args: core::fmt::Arguments) -> Self {
+        #[cfg(feature = "std")]
+        let message = args.to_string();
+        #[cfg(not(feature = "std"))]
+        let message = {
+            let mut writer = TruncatingWriter(String::new());
+            let _ = core::fmt::Write::write_fmt(&mut writer, args);
+            writer.0
+        };
+        Self::new(kind, message)
+    }
+    #[cfg(feature = "std")]
+    /// Create a new error with a message and additional structured data
+    pub fn with_data(kind: RpcErrorKind, message: String, data: serde_json::Value) -> Self {
         Self {
             kind,
             message: Some(message),
+            data: Some(data),
+        }
+    }
+    pub(crate) fn from_parts(kind: RpcErrorKind, message: Option<String>) -> Self {
+        Self {
+            kind,
+            message,
+            #[cfg(feature = "std")]
+            data: None,
         }
     }
     /// Get the error kind
@@ -169,6 +442,39 @@ impl RpcError {
     pub fn message(&self) -> Option<&str> {
         self.message.as_deref()
     }
+    #[cfg(feature = "std")]
+    /// Get the error data
+    pub fn data(&self) -> Option<&serde_json::Value> {
+        self.data.as_ref()
+    }
+    #[cfg(feature = "std")]
+    /// Try to de-serialize the error data into the given type, returns `None` if there is no data
+    pub fn data_as<T: DeserializeOwned>(&self) -> Option<Result<T, serde_json::Error>> {
+        self.data.as_ref().map(|data| T::deserialize(data))
+    }
+}
+
+/// Compares the error kind only (the message and data are ignored), e.g. to assert handler errors
+/// in tests
+impl PartialEq<RpcErrorKind> for RpcError {
+    fn eq(&self, other: &RpcErrorKind) -> bool {
+        self.kind == *other
+    }
+}
+
+/// Writes a message into a heapless string, dropping everything which does not fit
+#[cfg(not(feature = "std"))]
+struct TruncatingWriter(String);
+
+#[cfg(not(feature = "std"))]
+impl core::fmt::Write for TruncatingWriter {
+    fn write_str(&mut self, s: &str) -> core::fmt::Result {
+        for c in s.chars() {
+            // stops formatting when the string is full
+            self.0.push(c).map_err(|_| core::fmt::Error)?;
+        }
+        Ok(())
+    }
 }
 
 #[cfg(feature = "std")]
@@ -185,5 +491,150 @@ impl core::fmt::Display for RpcError {
 #[cfg(feature = "std")]
 impl std::error::Error for RpcError {}
 
+/// A message does not fit the string capacity (128 bytes in `no_std` mode, never returned in
+/// `std` mode)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CapacityError;
+
+impl core::fmt::Display for CapacityError {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        write!(f, "message exceeds the string capacity")
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for CapacityError {}
+
+/// Converts a message into the string type, fails if the message does not fit the capacity
+// never fails in std mode, the signature is shared with no_std
+#[cfg_attr(feature = "std", allow(clippy::unnecessary_wraps))]
+fn try_string(message: &str) -> Result<String, CapacityError> {
+    #[cfg(feature = "std")]
+    {
+        Ok(message.to_owned())
+    }
+    #[cfg(not(feature = "std"))]
+    {
+        String::try_from(message).map_err(|()| CapacityError)
+    }
+}
+
 /// RPC result type alias for RPC handler
 pub type RpcResult<R> = Result<R, RpcError>;
+
+#[cfg(feature = "std")]
+/// Converts application errors into [`RpcError`] with [`RpcErrorKind::InternalError`] kind and
+/// the error message, implemented for all `Display` types
+pub trait IntoRpcError {
+    /// Convert the error into an internal RPC error
+    fn into_rpc_error(self) -> RpcError;
+}
+
+#[cfg(feature = "std")]
+impl<E: core::fmt::Display> IntoRpcError for E {
+    fn into_rpc_error(self) -> RpcError {
+        RpcError::from_display(RpcErrorKind::InternalError, &self)
+    }
+}
+
+#[cfg(feature = "std")]
+/// Extension for results with application errors, e.g. `std::fs::read(path).internal_err()?` in
+/// handlers
+pub trait RpcResultExt<T> {
+    /// Map the error into an internal RPC error (see [`IntoRpcError`])
+    fn internal_err(self) -> RpcResult<T>;
+}
+
+#[cfg(feature = "std")]
+impl<T, E: core::fmt::Display> RpcResultExt<T> for Result<T, E> {
+    fn internal_err(self) -> RpcResult<T> {
+        self.map_err(IntoRpcError::into_rpc_error)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_rpc_error_eq() {
+        let error = RpcError::try_new(RpcErrorKind::InvalidParams, "invalid").unwrap();
+        assert_eq!(
+            error,
+            RpcError::try_new(RpcErrorKind::InvalidParams, "invalid").unwrap()
+        );
+        assert_eq!(
+            RpcError::INTERNAL_ERROR,
+            RpcError::new0(RpcErrorKind::InternalError)
+        );
+        assert_ne!(
+            error,
+            RpcError::try_new(RpcErrorKind::InvalidParams, "other").unwrap()
+        );
+        assert_ne!(
+            error,
+            RpcError::try_new(RpcErrorKind::InvalidRequest, "invalid").unwrap()
+        );
+    }
+
+    #[test]
+    fn test_rpc_error_kind_eq_by_code() {
+        assert_eq!(
+            RpcErrorKind::Custom(-32050),
+            RpcErrorKind::ServerError(-32050)
+        );
+        assert_eq!(
+            RpcErrorKind::from(i32::from(RpcErrorKind::Custom(-32050))),
+            RpcErrorKind::Custom(-32050)
+        );
+        assert!(RpcErrorKind::Custom(-32050).is_server_error());
+        assert_eq!(RpcErrorKind::Custom(-32700), RpcErrorKind::ParseError);
+        assert_ne!(RpcErrorKind::Custom(1), RpcErrorKind::Custom(2));
+    }
+    #[test]
+    fn test_rpc_error_kind_wide_codes() {
+        for code in [i32::from(i16::MIN) - 1, 100_000, i32::MIN, i32::MAX] {
+            let kind = RpcErrorKind::from(code);
+            assert_eq!(kind, RpcErrorKind::Custom(code));
+            assert_eq!(i32::from(kind), code);
+        }
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn test_rpc_error_wide_code_serialization() {
+        let error = RpcError::new(RpcErrorKind::Custom(-100_000), "custom".to_owned());
+        let payload = serde_json::to_string(&error).unwrap();
+        let error: RpcError = serde_json::from_str(&payload).unwrap();
+        assert_eq!(error.kind(), RpcErrorKind::Custom(-100_000));
+        assert_eq!(error.message(), Some("custom"));
+    }
+    #[test]
+    fn test_field_names() {
+        use field_names::{ERROR, ID, METHOD, PARAMS, RESULT};
+        let names = [ID, METHOD, PARAMS, RESULT, ERROR];
+        if cfg!(feature = "canonical") {
+            assert_eq!(names, ["id", "method", "params", "result", "error"]);
+        } else {
+            assert_eq!(names, ["i", "m", "p", "r", "e"]);
+        }
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn test_field_names_on_wire() {
+        let response = serde_json::to_value(response::Response::from_handler_response(
+            Id::from(1),
+            response::HandlerResponse::Ok(2),
+        ))
+        .unwrap();
+        assert_eq!(response[field_names::ID], 1);
+        assert_eq!(response[field_names::RESULT], 2);
+        let response = serde_json::to_value(response::Response::<i64>::error(
+            Id::from(1),
+            RpcError::INTERNAL_ERROR,
+        ))
+        .unwrap();
+        assert!(response.get(field_names::ERROR).is_some());
+    }
+}
@@ -3,69 +3,302 @@ use serde::{Deserialize, Serialize};
 use crate::{
     de_validate_version,
     response::{HandlerResponse, Response},
-    serialize_version, Id, RpcError, RpcErrorKind, String, VERSION_HEADER,
+    serialize_version, skip_none, try_string, CapacityError, Id, RpcError, RpcErrorKind, RpcId,
+    String, VERSION_HEADER,
 };
 
 #[cfg(feature = "canonical")]
 use crate::{ERR_INVALID_PROTOCOL_VERSION, JSONRPC_VERSION};
 
-#[derive(Serialize, Deserialize, Debug)]
+#[cfg(feature = "null-id")]
+use crate::de_nullable_id;
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
 #[serde(deny_unknown_fields)]
 /// JSON-RPC Request object
-pub struct Request<M> {
+pub struct Request<M, I = Id> {
     #[serde(
-        default,
         deserialize_with = "de_validate_version",
         serialize_with = "serialize_version",
-        skip_serializing_if = "Option::is_none"
+        skip_serializing_if = "skip_none"
+    )]
+    #[cfg_attr(
+        not(all(feature = "canonical", feature = "strict-version")),
+        serde(default)
     )]
     jsonrpc: Option<()>,
     #[cfg_attr(
         feature = "canonical",
-        serde(skip_serializing_if = "Option::is_none", alias = "i")
+        serde(skip_serializing_if = "skip_none", alias = "i")
     )]
     #[cfg_attr(
         not(feature = "canonical"),
-        serde(rename = "i", skip_serializing_if = "Option::is_none")
+        serde(rename = "i", skip_serializing_if = "skip_none", alias = "id")
     )]
-    pub(crate) id: Option<Id>,
+    #[cfg_attr(
+        feature = "null-id",
+        serde(
+            default = "Option::default",
+            deserialize_with = "de_nullable_id",
+            bound(deserialize = "I: Deserialize<'de>")
+        )
+    )]
+    pub(crate) id: Option<I>,
+    #[cfg(feature = "meta")]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub(crate) meta: Option<serde_json::Value>,
     #[cfg_attr(feature = "std", serde(flatten))]
     #[cfg_attr(not(feature = "std"), serde(rename = "p"))]
     pub(crate) method: M,
 }
 
-impl<'a, M> Request<M>
-where
-    M: Serialize + Deserialize<'a>,
-{
+impl<M, I> Request<M, I> {
     /// Create a new Request object with the given method with no ID (no response expected)
-    pub fn new0(method: M) -> Request<M> {
+    pub fn new0(method: M) -> Request<M, I> {
         Request {
             jsonrpc: VERSION_HEADER,
             id: None,
+            #[cfg(feature = "meta")]
+            meta: None,
             method,
         }
     }
     /// Create a new Request object with the given method and ID
-    pub fn new(id: Id, method: M) -> Request<M> {
+    pub fn new(id: I, method: M) -> Request<M, I> {
         Request {
             jsonrpc: VERSION_HEADER,
             id: Some(id),
+            #[cfg(feature = "meta")]
+            meta: None,
             method,
         }
     }
     /// Split the Request object into its parts (useful for 3rd party serialization)
-    pub fn into_parts(self) -> (Option<Id>, M) {
+    pub fn into_parts(self) -> (Option<I>, M) {
         (self.id, self.method)
     }
     /// Combine the parts into a Request object (useful for 3rd party de-serialization)
-    pub fn from_parts(id: Option<Id>, method: M) -> Request<M> {
+    pub fn from_parts(id: Option<I>, method: M) -> Request<M, I> {
         Request {
             jsonrpc: VERSION_HEADER,
             id,
+            #[cfg(feature = "meta")]
+            meta: None,
             method,
         }
     }
+    /// Get the request ID as `u32` (`None` for notifications, string and non-integer IDs)
+    pub fn id_as_u32(&self) -> Option<u32>
+    where
+        I: RpcId,
+    {
+        self.id.as_ref()?.as_u32()
+    }
+    #[cfg(feature = "meta")]
+    /// Attach metadata (e.g. a trace id or an auth token) to the request, serialized as `meta`
+    pub fn with_meta(mut self, meta: serde_json::Value) -> Self {
+        self.meta = Some(meta);
+        self
+    }
+    #[cfg(feature = "meta")]
+    /// Get the request metadata
+    pub fn meta(&self) -> Option<&serde_json::Value> {
+        self.meta.as_ref()
+    }
+    /// Returns true if the request has the `jsonrpc` version header (for created objects: true in
+    /// the canonical mode only)
+    pub fn has_version(&self) -> bool {
+        self.jsonrpc.is_some()
+    }
+}
+
+#[cfg(feature = "std")]
+const CANONICAL_KEYS: [(&str, &str); 3] = [("i", "id"), ("m", "method"), ("p", "params")];
+
+#[cfg(feature = "std")]
+impl<M> Request<M>
+where
+    M: serde::de::DeserializeOwned,
+{
+    /// Parse a JSON request in either canonical (`id`/`method`/`params`) or compact (`i`/`m`/`p`)
+    /// layout, regardless of the active mode. The payload keys are normalized to the canonical
+    /// layout first, if the method type does not accept it, to the compact one. Payloads which
+    /// mix both layouts are normalized as well, but if a payload contains both names of the same
+    /// field (e.g. `id` and `i`), it is rejected. If both attempts fail, the error of the
+    /// canonical attempt is returned.
+    pub fn from_slice_any(payload: &[u8]) -> Result<Self, serde_json::Error> {
+        let value: serde_json::Value = serde_json::from_slice(payload)?;
+        let serde_json::Value::Object(map) = value else {
+            return serde_json::from_value(value);
+        };
+        let normalize = |canonical: bool| -> Result<serde_json::Value, serde_json::Error> {
+            let mut normalized = serde_json::Map::new();
+            for (key, value) in &map {
+                let key = CANONICAL_KEYS
+                    .iter()
+                    .find_map(|(compact, full)| match (canonical, key.as_str()) {
+                        (true, k) if k == *compact => Some(*full),
+                        (false, k) if k == *full => Some(*compact),
+                        _ => None,
+                    })
+                    .unwrap_or(key);
+                if normalized.insert(key.to_owned(), value.clone()).is_some() {
+                    return Err(serde::de::Error::custom(format!(
+                        "duplicate field `{}`",
+                        key
+                    )));
+                }
+            }
+            Ok(serde_json::Value::Object(normalized))
+        };
+        match normalize(true).and_then(serde_json::from_value) {
+            Ok(request) => Ok(request),
+            Err(error) => normalize(false)
+                .and_then(serde_json::from_value)
+                .map_err(|_| error),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl<'de, M> Request<M>
+where
+    M: Deserialize<'de>,
+{
+    /// Parse and validate a request payload. Unlike plain de-serialization, failures are
+    /// classified: `InvalidParams` if the method is known but its params are missing (absent or
+    /// null) or invalid, `MethodNotFound` for unknown methods, `InvalidRequest` if the payload
+    /// has no method and `ParseError` if the payload is not a request object
+    pub fn validate<D: crate::dataformat::DataFormat>(
+        payload: &'de [u8],
+    ) -> Result<Self, RpcError> {
+        let error = match D::unpack::<Self>(payload) {
+            Ok(request) => return Ok(request),
+            Err(error) => error.to_string(),
+        };
+        let Ok(peek) = D::unpack::<MethodPeek>(payload) else {
+            // a request object with the method which is not a string
+            if D::unpack::<InvalidRequest>(payload).is_ok() {
+                return Err(RpcError::new(RpcErrorKind::InvalidRequest, error));
+            }
+            return Err(RpcError::new(RpcErrorKind::ParseError, error));
+        };
+        match peek.method {
+            Some(method) if is_method_known::<M>(&method) => {
+                let params = D::unpack::<ParamsPeek>(payload)
+                    .ok()
+                    .and_then(|peek| peek.params);
+                if params.is_some() {
+                    Err(RpcError::invalid_params(&error))
+                } else {
+                    Err(RpcError::invalid_params("params are missing"))
+                }
+            }
+            Some(method) => Err(RpcError::method_not_found(&method)),
+            None => Err(RpcError::new(RpcErrorKind::InvalidRequest, error)),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+#[derive(Deserialize)]
+/// Checks if params are present (not null), ignoring the rest of the payload
+struct ParamsPeek {
+    #[serde(default, alias = "p")]
+    params: Option<serde::de::IgnoredAny>,
+}
+
+#[cfg(feature = "std")]
+#[derive(Deserialize)]
+/// Parses the method name only, ignoring the rest of the payload
+pub(crate) struct MethodPeek<'a> {
+    #[serde(borrow, default, alias = "m")]
+    pub(crate) method: Option<std::borrow::Cow<'a, str>>,
+}
+
+#[cfg(feature = "std")]
+#[derive(Deserialize)]
+/// Parses the request id only, ignoring the rest of the payload
+pub(crate) struct IdPeek {
+    #[serde(default, alias = "i")]
+    pub(crate) id: Option<Id>,
+}
+
+#[cfg(feature = "std")]
+/// Method tag names, used to probe if a method is known
+const METHOD_TAGS: [&str; 2] = ["method", "m"];
+
+#[cfg(feature = "std")]
+/// Checks if the method name is known by the method type (de-serializes the method tag only)
+pub(crate) fn is_method_known<'de, M: Deserialize<'de>>(method: &str) -> bool {
+    for tag in METHOD_TAGS {
+        let deserializer = serde::de::value::MapDeserializer::<_, ProbeError>::new(
+            core::iter::once((tag, method)),
+        );
+        match M::deserialize(deserializer) {
+            Err(ProbeError::UnknownVariant) => return false,
+            // the method type uses another tag name
+            Err(ProbeError::TagMismatch) => {}
+            // the method is known, params are missing or invalid
+            Ok(_) | Err(ProbeError::Other) => return true,
+        }
+    }
+    false
+}
+
+#[cfg(feature = "std")]
+#[derive(Debug)]
+enum ProbeError {
+    UnknownVariant,
+    TagMismatch,
+    Other,
+}
+
+#[cfg(feature = "std")]
+impl core::fmt::Display for ProbeError {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            ProbeError::UnknownVariant => write!(f, "unknown variant"),
+            ProbeError::TagMismatch => write!(f, "method tag mismatch"),
+            ProbeError::Other => write!(f, "invalid method"),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for ProbeError {}
+
+#[cfg(feature = "std")]
+impl serde::de::Error for ProbeError {
+    fn custom<T: core::fmt::Display>(_msg: T) -> Self {
+        ProbeError::Other
+    }
+    fn unknown_variant(_variant: &str, _expected: &'static [&'static str]) -> Self {
+        ProbeError::UnknownVariant
+    }
+    fn invalid_value(unexp: serde::de::Unexpected, _exp: &dyn serde::de::Expected) -> Self {
+        // adjacently tagged enums with `deny_unknown_fields` reject unknown keys this way
+        match unexp {
+            serde::de::Unexpected::Str(field) if METHOD_TAGS.contains(&field) => {
+                ProbeError::TagMismatch
+            }
+            _ => ProbeError::Other,
+        }
+    }
+    fn unknown_field(field: &str, _expected: &'static [&'static str]) -> Self {
+        if METHOD_TAGS.contains(&field) {
+            ProbeError::TagMismatch
+        } else {
+            ProbeError::Other
+        }
+    }
+    fn missing_field(field: &'static str) -> Self {
+        if METHOD_TAGS.contains(&field) {
+            ProbeError::TagMismatch
+        } else {
+            ProbeError::Other
+        }
+    }
 }
 
 #[allow(clippy::module_name_repetitions)]
@@ -74,38 +307,190 @@ where
 pub struct InvalidRequest<'a> {
     #[allow(dead_code)]
     jsonrpc: Option<&'a str>,
+    #[serde(default, alias = "i")]
+    #[cfg_attr(feature = "null-id", serde(deserialize_with = "de_nullable_id"))]
     id: Option<Id>,
 }
 
 impl InvalidRequest<'_> {
+    /// Get the request ID (if could be parsed)
+    pub fn id(&self) -> Option<&Id> {
+        self.id.as_ref()
+    }
+    /// Get the error kind the response would be created with: `InvalidRequest` for an invalid
+    /// protocol version (canonical mode), otherwise `InvalidParams` if the request had a params
+    /// parse error with a known method, `MethodNotFound` if not
+    pub fn classify(&self, had_parse_error: bool) -> RpcErrorKind {
+        self.classify_as(if had_parse_error {
+            RpcErrorKind::InvalidParams
+        } else {
+            RpcErrorKind::MethodNotFound
+        })
+    }
+    #[allow(clippy::unused_self)]
+    fn classify_as(&self, kind: RpcErrorKind) -> RpcErrorKind {
+        #[cfg(feature = "canonical")]
+        if !matches!(self.jsonrpc, Some(jsonrpc) if jsonrpc == JSONRPC_VERSION) {
+            return RpcErrorKind::InvalidRequest;
+        }
+        kind
+    }
     /// Convert the InvalidRequest object into a Response object with the given error message
     pub fn into_response<R>(self, error: String) -> Option<Response<R>> {
-        if let Some(id) = self.id {
-            #[cfg(feature = "canonical")]
-            let (code, message) = if let Some(jsonrpc) = self.jsonrpc {
-                if jsonrpc == JSONRPC_VERSION {
-                    (RpcErrorKind::MethodNotFound, Some(error))
-                } else {
-                    (
-                        RpcErrorKind::InvalidRequest,
-                        #[allow(clippy::unnecessary_fallible_conversions)]
-                        ERR_INVALID_PROTOCOL_VERSION.try_into().ok(),
-                    )
-                }
-            } else {
-                (RpcErrorKind::InvalidRequest, None)
-            };
-            #[cfg(not(feature = "canonical"))]
-            let (code, message) = (RpcErrorKind::MethodNotFound, Some(error));
-            Some(Response::from_handler_response(
-                id,
-                HandlerResponse::Err(RpcError {
-                    kind: code,
-                    message,
-                }),
-            ))
+        let kind = self.classify(false);
+        self.into_classified_response(kind, error)
+    }
+    /// Convert the InvalidRequest object into a Response object with the given error message,
+    /// fails if the message does not fit the string capacity (128 bytes in `no_std` mode)
+    pub fn try_into_response<R>(self, error: &str) -> Result<Option<Response<R>>, CapacityError> {
+        Ok(self.into_response(try_string(error)?))
+    }
+    /// Convert the InvalidRequest object into a Response object with `InvalidParams` error (for
+    /// requests with a known method name but params which can not be parsed)
+    pub fn into_invalid_params_response<R>(self, error: String) -> Option<Response<R>> {
+        let kind = self.classify(true);
+        self.into_classified_response(kind, error)
+    }
+    /// Convert the InvalidRequest object into a Response object with `InvalidRequest` error (for
+    /// malformed requests, e.g. with the method field missing or not a string)
+    pub fn into_invalid_request_response<R>(self, error: String) -> Option<Response<R>> {
+        let kind = self.classify_as(RpcErrorKind::InvalidRequest);
+        self.into_classified_response(kind, error)
+    }
+    fn into_classified_response<R>(self, code: RpcErrorKind, error: String) -> Option<Response<R>> {
+        let id = self.id?;
+        #[cfg(feature = "canonical")]
+        let message = if matches!(self.jsonrpc, Some(jsonrpc) if jsonrpc == JSONRPC_VERSION) {
+            Some(error)
+        } else if self.jsonrpc.is_some() || cfg!(feature = "strict-version") {
+            // the protocol version is missing or invalid
+            #[allow(clippy::unnecessary_fallible_conversions)]
+            ERR_INVALID_PROTOCOL_VERSION.try_into().ok()
         } else {
             None
+        };
+        #[cfg(not(feature = "canonical"))]
+        let message = Some(error);
+        Some(Response::from_handler_response(
+            id,
+            HandlerResponse::Err(RpcError::from_parts(code, message)),
+        ))
+    }
+}
+
+#[cfg(all(test, feature = "std"))]
+mod tests {
+    use serde::{Deserialize, Serialize};
+
+    use super::*;
+
+    #[derive(Serialize, Deserialize, Debug, PartialEq)]
+    #[cfg_attr(
+        feature = "canonical",
+        serde(tag = "method", content = "params", deny_unknown_fields)
+    )]
+    #[cfg_attr(
+        not(feature = "canonical"),
+        serde(tag = "m", content = "p", deny_unknown_fields)
+    )]
+    enum TestMethod {
+        #[serde(rename = "hello")]
+        Hello { name: String },
+    }
+
+    fn assert_hello(payload: &str) {
+        let (id, method) = Request::<TestMethod>::from_slice_any(payload.as_bytes())
+            .unwrap()
+            .into_parts();
+        assert_eq!(id, Some(Id::from(1)));
+        assert_eq!(
+            method,
+            TestMethod::Hello {
+                name: "world".to_owned()
+            }
+        );
+    }
+
+    #[test]
+    fn test_from_slice_any_canonical() {
+        assert_hello(r#"{"jsonrpc":"2.0","id":1,"method":"hello","params":{"name":"world"}}"#);
+    }
+
+    #[test]
+    fn test_from_slice_any_compact() {
+        assert_hello(r#"{"jsonrpc":"2.0","i":1,"m":"hello","p":{"name":"world"}}"#);
+    }
+
+    #[test]
+    fn test_from_slice_any_mixed() {
+        assert_hello(r#"{"jsonrpc":"2.0","id":1,"m":"hello","params":{"name":"world"}}"#);
+        // both names of the same field
+        assert!(Request::<TestMethod>::from_slice_any(
+            br#"{"jsonrpc":"2.0","id":1,"i":2,"m":"hello","p":{"name":"world"}}"#
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn test_validate() {
+        use crate::field_names::{ID, METHOD, PARAMS};
+        let validate = |payload: String| {
+            Request::<TestMethod>::validate::<crate::dataformat::Json>(payload.as_bytes())
+                .unwrap_err()
+                .kind()
+        };
+        assert_eq!(
+            validate(format!(r#"{{"{}":1,"{}":"hello"}}"#, ID, METHOD)),
+            RpcErrorKind::InvalidParams
+        );
+        assert_eq!(
+            validate(format!(
+                r#"{{"{}":1,"{}":"hello","{}":{{"x":1}}}}"#,
+                ID, METHOD, PARAMS
+            )),
+            RpcErrorKind::InvalidParams
+        );
+        assert_eq!(
+            validate(format!(r#"{{"{}":1,"{}":"bye"}}"#, ID, METHOD)),
+            RpcErrorKind::MethodNotFound
+        );
+        assert_eq!(
+            validate(format!(r#"{{"{}":1}}"#, ID)),
+            RpcErrorKind::InvalidRequest
+        );
+        assert_eq!(
+            validate(format!(r#"{{"{}":1,"{}":7}}"#, ID, METHOD)),
+            RpcErrorKind::InvalidRequest
+        );
+        assert_eq!(validate("[1".to_owned()), RpcErrorKind::ParseError);
+    }
+
+    #[test]
+    fn test_version_field() {
+        let payload = |version: Option<&str>| {
+            let version = version
+                .map(|v| format!(r#""{}":"{}","#, crate::field_names::JSONRPC, v))
+                .unwrap_or_default();
+            format!(
+                r#"{{{}"{}":1,"{}":"hello","{}":{{"name":"world"}}}}"#,
+                version,
+                crate::field_names::ID,
+                crate::field_names::METHOD,
+                crate::field_names::PARAMS
+            )
+        };
+        let parse = |payload: String| serde_json::from_str::<Request<TestMethod>>(&payload);
+        assert!(parse(payload(Some("2.0"))).unwrap().has_version());
+        let absent = parse(payload(None));
+        if cfg!(all(feature = "canonical", feature = "strict-version")) {
+            assert!(absent.is_err());
+        } else {
+            assert!(!absent.unwrap().has_version());
         }
+        // the version is validated in the canonical mode only
+        assert_eq!(
+            parse(payload(Some("1.0"))).is_err(),
+            cfg!(feature = "canonical")
+        );
     }
 }
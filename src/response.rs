@@ -1,36 +1,42 @@
 use serde::{Deserialize, Serialize};
 
 use crate::{
-    de_validate_version, serialize_version, Id, RpcError, RpcErrorKind, RpcResult, String,
-    VERSION_HEADER,
+    de_validate_version, serialize_version, skip_none, try_string, CapacityError, Id, RpcError,
+    RpcErrorKind, RpcId, RpcResult, String, VERSION_HEADER,
 };
 
-#[derive(Serialize, Deserialize, Debug)]
-#[serde(deny_unknown_fields)]
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[cfg_attr(not(feature = "lenient"), serde(deny_unknown_fields))]
 /// JSON-RPC Response object
-pub struct Response<R> {
+pub struct Response<R, I = Id> {
     #[serde(
-        default,
         deserialize_with = "de_validate_version",
         serialize_with = "serialize_version",
-        skip_serializing_if = "Option::is_none"
+        skip_serializing_if = "skip_none"
+    )]
+    #[cfg_attr(
+        not(all(feature = "canonical", feature = "strict-version")),
+        serde(default)
     )]
     jsonrpc: Option<()>,
     #[cfg_attr(feature = "canonical", serde(alias = "i"))]
     #[cfg_attr(not(feature = "canonical"), serde(rename = "i"))]
-    id: Id,
+    id: I,
     #[cfg_attr(feature = "std", serde(flatten))]
     #[cfg_attr(not(feature = "std"), serde(rename = "p"))]
     handler_response: HandlerResponse<R>,
 }
 
-impl<R> Response<R> {
+impl<R, I> Response<R, I>
+where
+    I: RpcId,
+{
     /// Split the Response object into its parts (useful for 3rd party serialization)
-    pub fn into_parts(self) -> (Id, HandlerResponse<R>) {
+    pub fn into_parts(self) -> (I, HandlerResponse<R>) {
         (self.id, self.handler_response)
     }
     /// Combine the parts into a Response object (useful for 3rd party de-serialization)
-    pub fn from_parts(id: Id, handler_response: HandlerResponse<R>) -> Response<R> {
+    pub fn from_parts(id: I, handler_response: HandlerResponse<R>) -> Response<R, I> {
         Response {
             jsonrpc: VERSION_HEADER,
             id,
@@ -38,15 +44,24 @@ impl<R> Response<R> {
         }
     }
     /// Create a new Response object with the given ID and result from the RPC handler response
-    pub fn from_handler_response(id: Id, handler_response: HandlerResponse<R>) -> Response<R> {
+    pub fn from_handler_response(id: I, handler_response: HandlerResponse<R>) -> Response<R, I> {
         Response {
             jsonrpc: VERSION_HEADER,
             id,
             handler_response,
         }
     }
+    /// Create a new error Response object with the given ID and error (e.g. for errors detected
+    /// by the transport layer)
+    pub fn error(id: I, rpc_error: RpcError) -> Response<R, I> {
+        Response {
+            jsonrpc: VERSION_HEADER,
+            id,
+            handler_response: HandlerResponse::Err(rpc_error),
+        }
+    }
     /// Convert the response into an error response with the given error
-    pub fn into_error_response(self, rpc_error: RpcError) -> Response<R> {
+    pub fn into_error_response(self, rpc_error: RpcError) -> Response<R, I> {
         Response {
             jsonrpc: VERSION_HEADER,
             id: self.id,
@@ -54,29 +69,119 @@ impl<R> Response<R> {
         }
     }
     /// Get the ID of the response
-    pub fn id(&self) -> &Id {
+    pub fn id(&self) -> &I {
         &self.id
     }
+    /// Get the response ID as `u32` (`None` for string and non-integer IDs)
+    pub fn id_as_u32(&self) -> Option<u32> {
+        self.id.as_u32()
+    }
+    /// Get references to the response parts (see [`Self::into_parts`])
+    pub fn parts(&self) -> (&I, &HandlerResponse<R>) {
+        (&self.id, &self.handler_response)
+    }
+    /// Is the response an error
+    pub fn is_error(&self) -> bool {
+        self.handler_response.is_err()
+    }
+    /// Returns true if the response has the `jsonrpc` version header (for created objects: true
+    /// in the canonical mode only)
+    pub fn has_version(&self) -> bool {
+        self.jsonrpc.is_some()
+    }
+    #[cfg(feature = "std")]
+    pub(crate) fn handler_response(&self) -> &HandlerResponse<R> {
+        &self.handler_response
+    }
     /// Get the handler response
-    pub fn into_server_error_response(self, error: String) -> Response<R> {
+    pub fn into_server_error_response(self, error: String) -> Response<R, I> {
         Self::from_server_error(self.id, error)
     }
+    /// Create a new Response object with the given ID and error message, fails if the message
+    /// does not fit the string capacity (128 bytes in `no_std` mode)
+    pub fn try_from_server_error(id: I, error: &str) -> Result<Response<R, I>, CapacityError> {
+        Ok(Self::from_server_error(id, try_string(error)?))
+    }
     /// Create a new Response object with the given ID and error message
-    pub fn from_server_error(id: Id, error: String) -> Response<R> {
+    pub fn from_server_error(id: I, error: String) -> Response<R, I> {
         Response {
             jsonrpc: VERSION_HEADER,
             id,
-            handler_response: HandlerResponse::Err(RpcError {
-                kind: RpcErrorKind::InternalError,
-                message: Some(error),
-            }),
+            handler_response: HandlerResponse::Err(RpcError::new(
+                RpcErrorKind::InternalError,
+                error,
+            )),
+        }
+    }
+}
+
+#[allow(clippy::module_name_repetitions)]
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[cfg_attr(not(feature = "lenient"), serde(deny_unknown_fields))]
+/// A part of a streamed response. Chunks share the call id and are numbered with a sequence
+/// number, the last chunk is marked with a flag. Has additional `seq`/`last` fields (`s`/`l` in
+/// the compact mode), so chunks are not parsed as regular responses and vice versa
+pub struct ResponseChunk<R, I = Id> {
+    #[serde(
+        deserialize_with = "de_validate_version",
+        serialize_with = "serialize_version",
+        skip_serializing_if = "skip_none"
+    )]
+    #[cfg_attr(
+        not(all(feature = "canonical", feature = "strict-version")),
+        serde(default)
+    )]
+    jsonrpc: Option<()>,
+    #[cfg_attr(feature = "canonical", serde(alias = "i"))]
+    #[cfg_attr(not(feature = "canonical"), serde(rename = "i"))]
+    id: I,
+    #[cfg_attr(feature = "canonical", serde(rename = "seq", alias = "s"))]
+    #[cfg_attr(not(feature = "canonical"), serde(rename = "s"))]
+    seq: u64,
+    #[cfg_attr(feature = "canonical", serde(rename = "last", alias = "l"))]
+    #[cfg_attr(not(feature = "canonical"), serde(rename = "l"))]
+    last: bool,
+    #[cfg_attr(feature = "std", serde(flatten))]
+    #[cfg_attr(not(feature = "std"), serde(rename = "p"))]
+    handler_response: HandlerResponse<R>,
+}
+
+impl<R, I> ResponseChunk<R, I>
+where
+    I: RpcId,
+{
+    /// Create a new response chunk
+    pub fn new(id: I, seq: u64, last: bool, handler_response: HandlerResponse<R>) -> Self {
+        Self {
+            jsonrpc: VERSION_HEADER,
+            id,
+            seq,
+            last,
+            handler_response,
         }
     }
+    /// Get the ID of the chunk
+    pub fn id(&self) -> &I {
+        &self.id
+    }
+    /// Get the chunk sequence number
+    pub fn seq(&self) -> u64 {
+        self.seq
+    }
+    /// Is the chunk the last one
+    pub fn is_last(&self) -> bool {
+        self.last
+    }
+    /// Split the chunk into its parts: id, sequence number, the last chunk flag and the handler
+    /// response
+    pub fn into_parts(self) -> (I, u64, bool, HandlerResponse<R>) {
+        (self.id, self.seq, self.last, self.handler_response)
+    }
 }
 
 #[allow(clippy::module_name_repetitions)]
-#[derive(Serialize, Deserialize, Debug)]
-#[serde(deny_unknown_fields)]
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[cfg_attr(not(feature = "lenient"), serde(deny_unknown_fields))]
 /// RPC handler response object. Basically duplicates the standard Result object, required for the
 /// proper serialization
 pub enum HandlerResponse<R> {
@@ -118,10 +223,7 @@ impl<R> HandlerResponse<R> {
 impl<R> From<HandlerResponse<R>> for RpcResult<R> {
     fn from(res: HandlerResponse<R>) -> Self {
         match res {
-            HandlerResponse::Err(e) => Err(RpcError {
-                kind: e.kind,
-                message: e.message,
-            }),
+            HandlerResponse::Err(e) => Err(e),
             HandlerResponse::Ok(r) => Ok(r),
         }
     }
@@ -135,3 +237,101 @@ impl<R> From<RpcResult<R>> for HandlerResponse<R> {
         }
     }
 }
+
+#[cfg(feature = "std")]
+#[derive(Deserialize)]
+/// Checks if result and error fields are present (including null values), ignoring the rest of the
+/// payload
+pub(crate) struct ResultPeek {
+    #[serde(default, alias = "r", deserialize_with = "de_present")]
+    result: bool,
+    #[serde(default, alias = "e", deserialize_with = "de_present")]
+    error: bool,
+}
+
+#[cfg(feature = "std")]
+impl ResultPeek {
+    /// Returns `true` if the response has neither result nor error
+    pub(crate) fn is_empty(&self) -> bool {
+        !self.result && !self.error
+    }
+}
+
+#[cfg(feature = "std")]
+fn de_present<'de, D>(deserializer: D) -> Result<bool, D::Error>
+where
+    D: serde::Deserializer<'de>,
+{
+    serde::de::IgnoredAny::deserialize(deserializer).map(|_| true)
+}
+
+#[cfg(feature = "std")]
+#[derive(Debug, Clone)]
+/// Pre-serialized JSON result, which is packed into responses verbatim, with no re-encoding (e.g.
+/// for proxies forwarding results from an upstream). Can be used as the handler result type with
+/// JSON data formats only
+pub struct RawResult(Box<serde_json::value::RawValue>);
+
+#[cfg(feature = "std")]
+impl RawResult {
+    /// Create a raw result from JSON bytes (the bytes are validated once)
+    pub fn from_slice(json: &[u8]) -> Result<Self, serde_json::Error> {
+        serde_json::from_slice(json).map(RawResult)
+    }
+    /// Create a raw result from a JSON string (the string is validated once)
+    pub fn from_string(json: std::string::String) -> Result<Self, serde_json::Error> {
+        serde_json::value::RawValue::from_string(json).map(RawResult)
+    }
+    /// Get the JSON result as a string
+    pub fn get(&self) -> &str {
+        self.0.get()
+    }
+}
+
+#[cfg(feature = "std")]
+impl Serialize for RawResult {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: serde::Serializer,
+    {
+        self.0.serialize(serializer)
+    }
+}
+
+#[cfg(all(test, feature = "std"))]
+mod tests {
+    use super::*;
+    use crate::{
+        dataformat::{DataFormat, Json},
+        field_names,
+    };
+
+    #[test]
+    fn test_unknown_fields() {
+        let payload = format!(
+            r#"{{"{}":"2.0","{}":1,"{}":2,"meta":{{"node":"a"}}}}"#,
+            field_names::JSONRPC,
+            field_names::ID,
+            field_names::RESULT
+        );
+        let response = Json::unpack::<Response<i64>>(payload.as_bytes());
+        if cfg!(feature = "lenient") {
+            let response = response.unwrap();
+            assert_eq!(response.id(), &Id::from(1));
+            assert_eq!(response.handler_response().ok(), Some(&2));
+        } else {
+            assert!(response.is_err());
+        }
+    }
+    #[test]
+    fn test_raw_result() {
+        let raw = br#"{"b": 1,  "a":[1,2.50]}"#;
+        let response = Response::from_handler_response(
+            Id::from(1),
+            HandlerResponse::Ok(RawResult::from_slice(raw).unwrap()),
+        );
+        let payload = String::from_utf8(Json::pack(&response).unwrap()).unwrap();
+        assert!(payload.contains(core::str::from_utf8(raw).unwrap()));
+        assert!(RawResult::from_slice(b"{").is_err());
+    }
+}
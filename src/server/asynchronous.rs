@@ -0,0 +1,490 @@
+use core::{
+    fmt,
+    future::Future,
+    marker::PhantomData,
+    pin::Pin,
+    sync::atomic::{AtomicBool, Ordering},
+};
+use std::sync::Arc;
+use tracing::{debug_span, field, Instrument};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    dataformat::DataFormat, request::Request, response::Response, Id, RpcError, RpcErrorKind,
+    RpcResult,
+};
+
+use super::{
+    dedup::DedupCache,
+    pipeline::{Called, Pipeline},
+    AccessLogRecord, SourceFmt,
+};
+
+/// A boxed future, returned by async RPC handlers
+pub type BoxFuture<'a, T> = Pin<Box<dyn Future<Output = T> + Send + 'a>>;
+
+/// Error code of calls cancelled with [`CancellationToken`] (a server error code, see
+/// [`RpcErrorKind::ServerError`])
+pub const ERR_CODE_CANCELLED: i32 = -32001;
+
+/// Runtime-agnostic cooperative cancellation token, e.g. cancelled by the transport when the
+/// client disconnects. Handlers poll the token and abort long-running calls early
+#[derive(Debug, Clone, Default)]
+pub struct CancellationToken(Arc<AtomicBool>);
+
+impl CancellationToken {
+    /// Create a new token
+    pub fn new() -> Self {
+        Self::default()
+    }
+    /// Cancel the call (all clones of the token are cancelled)
+    pub fn cancel(&self) {
+        self.0.store(true, Ordering::SeqCst);
+    }
+    /// Returns `true` if the call has been cancelled
+    pub fn is_cancelled(&self) -> bool {
+        self.0.load(Ordering::SeqCst)
+    }
+    /// Returns [`ERR_CODE_CANCELLED`] error if the call has been cancelled, e.g.
+    /// `token.check()?;` in handlers
+    pub fn check(&self) -> RpcResult<()> {
+        if self.is_cancelled() {
+            Err(RpcError::new(
+                RpcErrorKind::Custom(ERR_CODE_CANCELLED),
+                "cancelled".to_owned(),
+            ))
+        } else {
+            Ok(())
+        }
+    }
+}
+
+/// Async JSON RPC server (runtime-agnostic). Payloads are processed with the same checks and
+/// hooks as in [`RpcServer`](super::RpcServer)
+#[allow(clippy::module_name_repetitions)]
+pub struct AsyncRpcServer<'a, RPC: AsyncRpcServerHandler<'a>, M, SRC, R> {
+    _phantom_a: PhantomData<&'a ()>,
+    _phantom_m: PhantomData<M>,
+    _phantom_src: PhantomData<SRC>,
+    _phantom_r: PhantomData<R>,
+    rpc: RPC,
+    pipeline: Pipeline,
+}
+
+impl<'a, RPC: AsyncRpcServerHandler<'a, Method = M, Result = R, Source = SRC>, M, SRC, R>
+    AsyncRpcServer<'a, RPC, M, SRC, R>
+where
+    M: Deserialize<'a> + 'a,
+    R: Serialize + 'a,
+    SRC: fmt::Debug + Clone,
+{
+    /// Create a new async JSON RPC server
+    pub fn new(rpc: RPC) -> Self {
+        Self {
+            _phantom_a: PhantomData,
+            _phantom_m: PhantomData,
+            _phantom_src: PhantomData,
+            _phantom_r: PhantomData,
+            rpc,
+            pipeline: Pipeline::default(),
+        }
+    }
+    /// Emit an access log record for each request payload processed into the given sink
+    pub fn with_access_log(
+        mut self,
+        sink: impl Fn(&AccessLogRecord) + Send + Sync + 'static,
+    ) -> Self {
+        self.pipeline.access_log = Some(Box::new(sink));
+        self
+    }
+    /// Reject requests which contain duplicate keys in any object (see
+    /// [`RpcServer::with_duplicate_keys_rejected`](super::RpcServer::with_duplicate_keys_rejected))
+    pub fn with_duplicate_keys_rejected(mut self) -> Self {
+        self.pipeline.reject_duplicate_keys = true;
+        self
+    }
+    /// Reject requests for methods which do not pass the filter with `MethodNotFound` error (see
+    /// [`RpcServer::with_method_filter`](super::RpcServer::with_method_filter))
+    pub fn with_method_filter(
+        mut self,
+        filter: impl Fn(&str) -> bool + Send + Sync + 'static,
+    ) -> Self {
+        self.pipeline.method_filter = Some(Box::new(filter));
+        self
+    }
+    /// Reject request payloads longer than the given number of bytes with `InvalidRequest` error
+    /// before de-serializing them (the default is unlimited)
+    pub fn with_max_payload_len(mut self, max_payload_len: usize) -> Self {
+        self.pipeline.max_payload_len = Some(max_payload_len);
+        self
+    }
+    /// Remember responses of the last `capacity` calls per source and id (see
+    /// [`RpcServer::with_dedup_cache`](super::RpcServer::with_dedup_cache)). Responses of
+    /// cancelled calls ([`ERR_CODE_CANCELLED`] errors) are not cached
+    pub fn with_dedup_cache(mut self, capacity: usize) -> Self {
+        self.pipeline.dedup_cache = Some(DedupCache::new(capacity));
+        self
+    }
+    /// Handle a JSON RPC request
+    ///
+    /// Notifications for methods which require an acknowledgement (see
+    /// [`AsyncRpcServerHandler::ack_required`]) get a synthetic server-assigned id and a response
+    pub async fn handle_request(&'a self, request: Request<M>, source: SRC) -> Option<Response<R>> {
+        let span = debug_span!("rpc_request", source = %SourceFmt(&source), method = field::Empty);
+        self.call(request, source, |id, meta, method, source| {
+            self.dispatch(id, meta, method, source)
+        })
+        .instrument(span)
+        .await
+        .into_response()
+    }
+    /// Handle a JSON RPC request with a cancellation token (see
+    /// [`AsyncRpcServerHandler::handle_call_cancellable`]). The handler gets no request id and
+    /// metadata, `handle_call_with_id`/`handle_call_with_meta` are not called
+    pub async fn handle_request_cancellable(
+        &'a self,
+        request: Request<M>,
+        source: SRC,
+        token: CancellationToken,
+    ) -> Option<Response<R>> {
+        let span = debug_span!("rpc_request", source = %SourceFmt(&source), method = field::Empty);
+        self.call(request, source, |_, _, method, source| {
+            self.rpc.handle_call_cancellable(method, source, token)
+        })
+        .instrument(span)
+        .await
+        .into_response()
+    }
+    async fn call<H>(&'a self, request: Request<M>, source: SRC, handle: H) -> Called<R>
+    where
+        H: FnOnce(Option<Id>, Option<serde_json::Value>, M, SRC) -> BoxFuture<'a, RpcResult<R>>,
+    {
+        let (call, method) = match self.pipeline.begin_call(
+            request,
+            |method| self.rpc.ack_required(method),
+            |method| self.rpc.before_call(method, &source),
+        ) {
+            Ok(v) => v,
+            Err(called) => return called,
+        };
+        let result = handle(call.id.clone(), call.meta.clone(), method, source.clone()).await;
+        // cancelled calls are not cached, so the client can retry them
+        let cancelled = matches!(&result, Err(e) if i32::from(e.kind()) == ERR_CODE_CANCELLED);
+        let called = call.finish(result, |v| self.rpc.after_call(v, &source));
+        if cancelled {
+            called.into_unhandled()
+        } else {
+            called
+        }
+    }
+    #[allow(unused_variables)]
+    fn dispatch(
+        &'a self,
+        id: Option<Id>,
+        meta: Option<serde_json::Value>,
+        method: M,
+        source: SRC,
+    ) -> BoxFuture<'a, RpcResult<R>> {
+        #[cfg(feature = "meta")]
+        {
+            self.rpc.handle_call_with_meta(id, meta, method, source)
+        }
+        #[cfg(not(feature = "meta"))]
+        {
+            self.rpc.handle_call_with_id(id, method, source)
+        }
+    }
+    /// Handle a JSON RPC request from a payload
+    pub async fn handle_request_payload<D>(
+        &'a self,
+        payload: &'a [u8],
+        source: SRC,
+    ) -> Option<Vec<u8>>
+    where
+        D: DataFormat,
+    {
+        self.handle_payload::<D, _>(payload, source, |id, meta, method, source| {
+            self.dispatch(id, meta, method, source)
+        })
+        .await
+    }
+    /// Handle a JSON RPC request from a payload with a cancellation token, which should be
+    /// cancelled by the transport (e.g. when the client disconnects). The call is handled with
+    /// [`AsyncRpcServerHandler::handle_call_cancellable`], which gets no request id and metadata
+    /// (`handle_call_with_id`/`handle_call_with_meta` are not called)
+    pub async fn handle_request_payload_cancellable<D>(
+        &'a self,
+        payload: &'a [u8],
+        source: SRC,
+        token: CancellationToken,
+    ) -> Option<Vec<u8>>
+    where
+        D: DataFormat,
+    {
+        self.handle_payload::<D, _>(payload, source, |_, _, method, source| {
+            self.rpc.handle_call_cancellable(method, source, token)
+        })
+        .await
+    }
+    async fn handle_payload<D, H>(
+        &'a self,
+        payload: &'a [u8],
+        source: SRC,
+        handle: H,
+    ) -> Option<Vec<u8>>
+    where
+        D: DataFormat,
+        H: FnOnce(Option<Id>, Option<serde_json::Value>, M, SRC) -> BoxFuture<'a, RpcResult<R>>,
+    {
+        let context = match self.pipeline.begin_payload::<D, _>(payload, &source) {
+            Ok(context) => context,
+            Err(cached) => return Some(cached),
+        };
+        let prepared = {
+            let _enter = context.span().enter();
+            self.pipeline
+                .prepare::<D, M, R, _>(payload, &source, context.method())
+        };
+        let processed = match prepared {
+            Ok(request) => self
+                .call(request, source, handle)
+                .instrument(context.span().clone())
+                .await
+                .into_processed::<D>(),
+            Err(processed) => processed,
+        };
+        self.pipeline.finish_payload(context, payload, processed)
+    }
+}
+
+/// Async RPC server trait
+#[allow(clippy::module_name_repetitions)]
+pub trait AsyncRpcServerHandler<'a> {
+    /// Methods to handle
+    type Method: Deserialize<'a>;
+    /// Result of the methods (serialize-only)
+    type Result: Serialize;
+    /// Source of the call (IP address, etc.)
+    type Source;
+
+    /// Called before each call (e.g. for authentication or rate limiting). If an error is
+    /// returned, the call is not handled and the error is sent back to the client instead.
+    #[allow(unused_variables)]
+    fn before_call(&self, method: &Self::Method, source: &Self::Source) -> RpcResult<()> {
+        Ok(())
+    }
+    /// A method to handle calls, usually implemented as `Box::pin(async move { ... })`
+    fn handle_call(
+        &'a self,
+        method: Self::Method,
+        source: Self::Source,
+    ) -> BoxFuture<'a, RpcResult<Self::Result>>;
+    /// A method to handle calls with access to the request id (`None` for notifications). Called
+    /// by [`AsyncRpcServer::handle_request`] and [`AsyncRpcServer::handle_request_payload`], the
+    /// cancellable entry points call [`AsyncRpcServerHandler::handle_call_cancellable`] instead.
+    /// The default ignores the id and calls [`AsyncRpcServerHandler::handle_call`]
+    #[allow(unused_variables)]
+    fn handle_call_with_id(
+        &'a self,
+        id: Option<Id>,
+        method: Self::Method,
+        source: Self::Source,
+    ) -> BoxFuture<'a, RpcResult<Self::Result>> {
+        self.handle_call(method, source)
+    }
+    #[cfg(feature = "meta")]
+    /// A method to handle calls with access to the request id and metadata (see
+    /// [`Request::with_meta`]). Called instead of [`AsyncRpcServerHandler::handle_call_with_id`]
+    /// by the same entry points, the default ignores the metadata and calls
+    /// [`AsyncRpcServerHandler::handle_call_with_id`]
+    #[allow(unused_variables)]
+    fn handle_call_with_meta(
+        &'a self,
+        id: Option<Id>,
+        meta: Option<serde_json::Value>,
+        method: Self::Method,
+        source: Self::Source,
+    ) -> BoxFuture<'a, RpcResult<Self::Result>> {
+        self.handle_call_with_id(id, method, source)
+    }
+    /// A method to handle calls with a cancellation token, long-running handlers should poll the
+    /// token (e.g. with [`CancellationToken::check`]). The default implementation ignores the
+    /// token and calls [`AsyncRpcServerHandler::handle_call`]
+    #[allow(unused_variables)]
+    fn handle_call_cancellable(
+        &'a self,
+        method: Self::Method,
+        source: Self::Source,
+        token: CancellationToken,
+    ) -> BoxFuture<'a, RpcResult<Self::Result>> {
+        self.handle_call(method, source)
+    }
+    /// Called with each successful call result before it is serialized, errors are not passed.
+    /// The default returns the result as-is
+    #[allow(unused_variables)]
+    fn after_call(&self, result: Self::Result, source: &Self::Source) -> Self::Result {
+        result
+    }
+    /// Returns true if a notification for the method must still be acknowledged (see
+    /// [`RpcServerHandler::ack_required`](super::RpcServerHandler::ack_required)). The default
+    /// is false.
+    #[allow(unused_variables)]
+    fn ack_required(&self, method: &Self::Method) -> bool {
+        false
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use core::sync::atomic::AtomicUsize;
+
+    use super::*;
+    use crate::{
+        dataformat::Json,
+        server::tests::{client, raw_request, unpack_response, TestMethod},
+    };
+
+    #[derive(Default)]
+    struct TestRpc {
+        calls: AtomicUsize,
+    }
+
+    impl<'a> AsyncRpcServerHandler<'a> for TestRpc {
+        type Method = TestMethod;
+        type Result = i64;
+        type Source = &'static str;
+
+        fn before_call(&self, method: &TestMethod, _source: &Self::Source) -> RpcResult<()> {
+            if matches!(method, TestMethod::Test {}) {
+                return Err(RpcError::new(
+                    RpcErrorKind::Custom(-32029),
+                    "denied".to_owned(),
+                ));
+            }
+            Ok(())
+        }
+        fn handle_call(
+            &'a self,
+            method: TestMethod,
+            _source: Self::Source,
+        ) -> BoxFuture<'a, RpcResult<i64>> {
+            Box::pin(async move {
+                self.calls.fetch_add(1, Ordering::SeqCst);
+                match method {
+                    TestMethod::Test {} | TestMethod::Ack {} => Ok(0),
+                    TestMethod::Add { a, b } => Ok(a + b),
+                }
+            })
+        }
+        fn handle_call_cancellable(
+            &'a self,
+            method: TestMethod,
+            source: Self::Source,
+            token: CancellationToken,
+        ) -> BoxFuture<'a, RpcResult<i64>> {
+            Box::pin(async move {
+                token.check()?;
+                self.handle_call(method, source).await
+            })
+        }
+        fn after_call(&self, result: i64, _source: &Self::Source) -> i64 {
+            result * 10
+        }
+        fn ack_required(&self, method: &TestMethod) -> bool {
+            matches!(method, TestMethod::Ack {})
+        }
+    }
+
+    #[tokio::test]
+    async fn test_pipeline_hooks() {
+        let server = AsyncRpcServer::new(TestRpc::default()).with_max_payload_len(64);
+        let client = client();
+        let req = client.request(TestMethod::Add { a: 1, b: 2 }).unwrap();
+        let response = unpack_response(
+            &server
+                .handle_request_payload::<Json>(req.payload(), "test")
+                .await
+                .unwrap(),
+        );
+        assert_eq!(response.handler_response().ok(), Some(&30));
+        // rejected by before_call
+        let req = client.request(TestMethod::Test {}).unwrap();
+        let response = unpack_response(
+            &server
+                .handle_request_payload::<Json>(req.payload(), "test")
+                .await
+                .unwrap(),
+        );
+        assert_eq!(
+            i32::from(response.handler_response().err().unwrap().kind()),
+            -32029
+        );
+        // notifications which require an acknowledgement get a response
+        let notification = client.request0(TestMethod::Ack {}).unwrap();
+        let response = unpack_response(
+            &server
+                .handle_request_payload::<Json>(notification.payload(), "test")
+                .await
+                .unwrap(),
+        );
+        assert_eq!(response.id(), &Id::from("ack-0"));
+        // oversized payloads are not handled
+        let payload = raw_request(
+            Some("1"),
+            Some("\"add\""),
+            Some(&format!(r#"{{"a":1,"b":2,"c":"{}"}}"#, "x".repeat(64))),
+        );
+        let response = unpack_response(
+            &server
+                .handle_request_payload::<Json>(&payload, "test")
+                .await
+                .unwrap(),
+        );
+        assert_eq!(
+            response.handler_response().err().unwrap().kind(),
+            RpcErrorKind::InvalidRequest
+        );
+        assert_eq!(server.rpc.calls.load(Ordering::SeqCst), 2);
+    }
+
+    #[tokio::test]
+    async fn test_cancelled_not_cached() {
+        let server = AsyncRpcServer::new(TestRpc::default()).with_dedup_cache(16);
+        let req = client().request(TestMethod::Add { a: 1, b: 2 }).unwrap();
+        let token = CancellationToken::new();
+        token.cancel();
+        let response = unpack_response(
+            &server
+                .handle_request_payload_cancellable::<Json>(req.payload(), "test", token)
+                .await
+                .unwrap(),
+        );
+        assert_eq!(
+            i32::from(response.handler_response().err().unwrap().kind()),
+            ERR_CODE_CANCELLED
+        );
+        // the retry is handled again
+        let response = unpack_response(
+            &server
+                .handle_request_payload_cancellable::<Json>(
+                    req.payload(),
+                    "test",
+                    CancellationToken::new(),
+                )
+                .await
+                .unwrap(),
+        );
+        assert_eq!(response.handler_response().ok(), Some(&30));
+        assert_eq!(server.rpc.calls.load(Ordering::SeqCst), 1);
+        // completed calls are cached
+        let response = unpack_response(
+            &server
+                .handle_request_payload::<Json>(req.payload(), "test")
+                .await
+                .unwrap(),
+        );
+        assert_eq!(response.handler_response().ok(), Some(&30));
+        assert_eq!(server.rpc.calls.load(Ordering::SeqCst), 1);
+    }
+}
@@ -0,0 +1,77 @@
+use std::{
+    collections::{HashMap, VecDeque},
+    sync::{Mutex, PoisonError},
+};
+
+pub(super) type DedupKey = (String, String);
+
+/// Recently seen request responses, keyed by the call source and id. The oldest entries are
+/// evicted when the capacity is reached
+pub(super) struct DedupCache {
+    capacity: usize,
+    entries: Mutex<DedupEntries>,
+}
+
+#[derive(Default)]
+struct DedupEntries {
+    responses: HashMap<DedupKey, Vec<u8>>,
+    order: VecDeque<DedupKey>,
+}
+
+impl DedupCache {
+    pub(super) fn new(capacity: usize) -> Self {
+        Self {
+            capacity,
+            entries: <_>::default(),
+        }
+    }
+    pub(super) fn get(&self, key: &DedupKey) -> Option<Vec<u8>> {
+        self.entries
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .responses
+            .get(key)
+            .cloned()
+    }
+    pub(super) fn insert(&self, key: DedupKey, response: Vec<u8>) {
+        if self.capacity == 0 {
+            return;
+        }
+        let mut entries = self.entries.lock().unwrap_or_else(PoisonError::into_inner);
+        if entries.responses.insert(key.clone(), response).is_some() {
+            return;
+        }
+        entries.order.push_back(key);
+        while entries.order.len() > self.capacity {
+            if let Some(evicted) = entries.order.pop_front() {
+                entries.responses.remove(&evicted);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn key(id: &str) -> DedupKey {
+        ("source".to_owned(), id.to_owned())
+    }
+
+    #[test]
+    fn test_eviction() {
+        let cache = DedupCache::new(2);
+        assert_eq!(cache.get(&key("1")), None);
+        cache.insert(key("1"), b"one".to_vec());
+        cache.insert(key("2"), b"two".to_vec());
+        assert_eq!(cache.get(&key("1")), Some(b"one".to_vec()));
+        cache.insert(key("3"), b"three".to_vec());
+        // the oldest entry is evicted
+        assert_eq!(cache.get(&key("1")), None);
+        assert_eq!(cache.get(&key("2")), Some(b"two".to_vec()));
+        assert_eq!(cache.get(&key("3")), Some(b"three".to_vec()));
+        let cache = DedupCache::new(0);
+        cache.insert(key("1"), b"one".to_vec());
+        assert_eq!(cache.get(&key("1")), None);
+    }
+}
@@ -0,0 +1,1151 @@
+use core::{cell::RefCell, fmt, marker::PhantomData};
+use std::{
+    collections::BTreeSet,
+    io::Write,
+    sync::{Mutex, PoisonError},
+};
+use tracing::{debug_span, error, field};
+
+use serde::{
+    de::{self, MapAccess, SeqAccess, Visitor},
+    Deserialize, Deserializer, Serialize,
+};
+
+use self::{
+    dedup::DedupCache,
+    pipeline::{Call, Called, Pipeline},
+};
+use crate::{
+    dataformat::DataFormat,
+    request::{is_method_known, InvalidRequest, MethodPeek, Request},
+    response::{HandlerResponse, Response},
+    Id, RpcError, RpcErrorKind, RpcResult,
+};
+
+mod dedup;
+mod pipeline;
+
+#[cfg(feature = "async")]
+mod asynchronous;
+#[cfg(feature = "async")]
+pub use asynchronous::{
+    AsyncRpcServer, AsyncRpcServerHandler, BoxFuture, CancellationToken, ERR_CODE_CANCELLED,
+};
+
+const ERR_FAILED_TO_PARSE: &str = "Failed to parse RPC request";
+
+/// Formats call sources for logs with `Debug`, but plain strings (with no characters to escape)
+/// are printed unquoted, so `&str`/`String` sources look the same as with `Display`
+pub(crate) struct SourceFmt<'a, S>(pub(crate) &'a S);
+
+impl<S: fmt::Debug> fmt::Display for SourceFmt<'_, S> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let debug = format!("{:?}", self.0);
+        match debug.strip_prefix('"').and_then(|s| s.strip_suffix('"')) {
+            Some(plain) if !plain.contains('\\') => f.write_str(plain),
+            _ => f.write_str(&debug),
+        }
+    }
+}
+
+/// JSON RPC server
+#[allow(clippy::module_name_repetitions)]
+pub struct RpcServer<'a, RPC: RpcServerHandler<'a>, M, SRC, R> {
+    _phantom_a: PhantomData<&'a ()>,
+    _phantom_m: PhantomData<M>,
+    _phantom_src: PhantomData<SRC>,
+    _phantom_r: PhantomData<R>,
+    rpc: RPC,
+    pipeline: Pipeline,
+}
+
+/// Access log record, emitted once per request payload processed
+#[derive(Serialize, Debug)]
+pub struct AccessLogRecord {
+    /// Source of the call (debug-formatted, plain strings are not quoted)
+    pub source: String,
+    /// Method name (if can be parsed from the payload)
+    pub method: Option<String>,
+    /// Request id (if present)
+    pub id: Option<Id>,
+    /// Call outcome
+    pub outcome: AccessLogOutcome,
+    /// Error code (if the call failed)
+    pub code: Option<RpcErrorKind>,
+    /// Processing duration in microseconds
+    pub duration_us: u64,
+    /// Request payload size in bytes
+    pub request_bytes: usize,
+    /// Response payload size in bytes (zero if no response is sent)
+    pub response_bytes: usize,
+}
+
+impl AccessLogRecord {
+    /// Format the record as a JSON line (with the trailing new-line character)
+    pub fn to_json_line(&self) -> String {
+        let mut line = serde_json::to_string(self).unwrap_or_default();
+        line.push('\n');
+        line
+    }
+}
+
+/// Access log call outcome
+#[derive(Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum AccessLogOutcome {
+    /// The handler returned a result
+    Ok,
+    /// The handler returned an error
+    Error,
+    /// The request could not be parsed
+    Invalid,
+}
+
+/// Creates an access log sink which writes records as JSON lines into the given writer
+pub fn access_log_json_lines<W>(writer: W) -> impl Fn(&AccessLogRecord) + Send + Sync
+where
+    W: Write + Send + 'static,
+{
+    let writer = Mutex::new(writer);
+    move |record| {
+        // a panic in another writer thread does not stop logging
+        let mut writer = writer.lock().unwrap_or_else(PoisonError::into_inner);
+        if let Err(error) = writer.write_all(record.to_json_line().as_bytes()) {
+            error!(%error, "Failed to write access log record");
+        }
+    }
+}
+
+impl<'a, RPC: RpcServerHandler<'a, Method = M, Result = R, Source = SRC>, M, SRC, R>
+    RpcServer<'a, RPC, M, SRC, R>
+where
+    M: Deserialize<'a> + 'a,
+    R: Serialize + 'a,
+    SRC: fmt::Debug,
+{
+    /// Create a new JSON RPC server
+    pub fn new(rpc: RPC) -> Self {
+        Self {
+            _phantom_a: PhantomData,
+            _phantom_m: PhantomData,
+            _phantom_src: PhantomData,
+            _phantom_r: PhantomData,
+            rpc,
+            pipeline: Pipeline::default(),
+        }
+    }
+    /// Emit an access log record for each request payload processed into the given sink
+    pub fn with_access_log(
+        mut self,
+        sink: impl Fn(&AccessLogRecord) + Send + Sync + 'static,
+    ) -> Self {
+        self.pipeline.access_log = Some(Box::new(sink));
+        self
+    }
+    /// Reject requests which contain duplicate keys in any object (e.g. in params) with
+    /// `InvalidRequest` error instead of silently taking the last value (hardens against
+    /// parameter smuggling)
+    pub fn with_duplicate_keys_rejected(mut self) -> Self {
+        self.pipeline.reject_duplicate_keys = true;
+        self
+    }
+    /// Reject requests for methods which do not pass the filter with `MethodNotFound` error. The
+    /// filter is applied to the method name of raw payloads (see
+    /// [`RpcServer::handle_request_payload`]) before params are de-serialized, payloads with no
+    /// readable method name are rejected as invalid requests
+    pub fn with_method_filter(
+        mut self,
+        filter: impl Fn(&str) -> bool + Send + Sync + 'static,
+    ) -> Self {
+        self.pipeline.method_filter = Some(Box::new(filter));
+        self
+    }
+    /// Reject request payloads longer than the given number of bytes with `InvalidRequest` error
+    /// before de-serializing them (the default is unlimited)
+    pub fn with_max_payload_len(mut self, max_payload_len: usize) -> Self {
+        self.pipeline.max_payload_len = Some(max_payload_len);
+        self
+    }
+    /// Remember responses of the last `capacity` calls per source and id, redelivered requests
+    /// get the cached response without calling the handler again (at-most-once semantics over
+    /// transports which may redeliver). Only responses of calls which reached the handler are
+    /// cached, so requests rejected before (e.g. by [`RpcServerHandler::before_call`]) are
+    /// processed again. Cached responses are returned before logging and tracing
+    pub fn with_dedup_cache(mut self, capacity: usize) -> Self {
+        self.pipeline.dedup_cache = Some(DedupCache::new(capacity));
+        self
+    }
+    /// Handle a JSON RPC request
+    ///
+    /// Notifications for methods which require an acknowledgement (see
+    /// [`RpcServerHandler::ack_required`]) get a synthetic server-assigned id and a response,
+    /// which deviates from the strict JSON-RPC notification semantics.
+    pub fn handle_request(&'a self, request: Request<M>, source: SRC) -> Option<Response<R>> {
+        let span = debug_span!("rpc_request", source = %SourceFmt(&source), method = field::Empty);
+        let _enter = span.enter();
+        self.call(request, source, |id, meta, method, source| {
+            self.dispatch(id, meta, method, source)
+        })
+        .into_response()
+    }
+    fn begin_call(&'a self, request: Request<M>, source: &SRC) -> Result<(Call, M), Called<R>> {
+        self.pipeline.begin_call(
+            request,
+            |method| self.rpc.ack_required(method),
+            |method| self.rpc.before_call(method, source),
+        )
+    }
+    fn call<H>(&'a self, request: Request<M>, source: SRC, handle: H) -> Called<R>
+    where
+        H: FnOnce(Option<&Id>, Option<&serde_json::Value>, M, SRC) -> RpcResult<R>,
+    {
+        let (call, method) = match self.begin_call(request, &source) {
+            Ok(v) => v,
+            Err(called) => return called,
+        };
+        let result = handle(call.id.as_ref(), call.meta.as_ref(), method, source);
+        // the source is moved into the handler, so `after_call` is applied to calls with
+        // borrowed sources only (see `handle_request_ref`)
+        call.finish(result, |v| v)
+    }
+    #[allow(unused_variables)]
+    fn dispatch(
+        &'a self,
+        id: Option<&Id>,
+        meta: Option<&serde_json::Value>,
+        method: M,
+        source: SRC,
+    ) -> RpcResult<R> {
+        #[cfg(feature = "meta")]
+        {
+            self.rpc.handle_call_with_meta(id, meta, method, source)
+        }
+        #[cfg(not(feature = "meta"))]
+        {
+            self.rpc.handle_call_with_id(id, method, source)
+        }
+    }
+    /// Handle a JSON RPC request from a payload
+    ///
+    /// Requests are handled inside `rpc_request` debug tracing spans (with `source` and `method`
+    /// fields), a debug event with the call duration and outcome is emitted for each call.
+    pub fn handle_request_payload<D>(&'a self, payload: &'a [u8], source: SRC) -> Option<Vec<u8>>
+    where
+        D: DataFormat,
+    {
+        self.handle_payload::<D, _>(payload, source, |id, meta, method, source| {
+            self.dispatch(id, meta, method, source)
+        })
+    }
+    /// Handle a JSON batch payload (a top-level array of requests) lazily: each request is
+    /// processed only when the iterator is advanced, so a transport can flush responses
+    /// incrementally. Items are yielded strictly in the order of the requests in the batch,
+    /// notifications yield `None`. Payloads which are not an array are processed as a single
+    /// request. If the batch is empty or the whole payload exceeds the maximum length (see
+    /// [`Self::with_max_payload_len`]), a single `InvalidRequest` response with null id is
+    /// yielded. Use [`collect_batch_responses`] to frame the responses as an array
+    ///
+    /// JSON only, as requests are split into raw slices of the payload without re-serializing
+    pub fn handle_json_batch_iter(
+        &'a self,
+        payload: &'a [u8],
+        source: SRC,
+    ) -> impl Iterator<Item = Option<Vec<u8>>> + 'a
+    where
+        SRC: Clone + 'a,
+    {
+        let invalid = |message: &str| {
+            let response = Response::<R>::error(
+                Id::Null,
+                RpcError::new(RpcErrorKind::InvalidRequest, message.to_owned()),
+            );
+            pack_response::<crate::dataformat::Json, R>(&response)
+        };
+        let (rejected, requests): (Option<Vec<u8>>, Vec<&'a [u8]>) =
+            if self.pipeline.is_oversized(payload) {
+                error!(
+                    source = %SourceFmt(&source),
+                    len = payload.len(),
+                    "RPC batch payload is too large"
+                );
+                (invalid("request payload is too large"), Vec::new())
+            } else {
+                match serde_json::from_slice::<Vec<&'a serde_json::value::RawValue>>(payload) {
+                    // an empty batch is a single invalid request (JSON-RPC 2.0)
+                    Ok(batch) if batch.is_empty() => (invalid("empty batch"), Vec::new()),
+                    Ok(batch) => (
+                        None,
+                        batch.into_iter().map(|raw| raw.get().as_bytes()).collect(),
+                    ),
+                    Err(_) => (None, vec![payload]),
+                }
+            };
+        rejected
+            .map(Some)
+            .into_iter()
+            .chain(requests.into_iter().map(move |request| {
+                self.handle_request_payload::<crate::dataformat::Json>(request, source.clone())
+            }))
+    }
+    fn handle_payload<D, H>(&'a self, payload: &'a [u8], source: SRC, handle: H) -> Option<Vec<u8>>
+    where
+        D: DataFormat,
+        H: FnOnce(Option<&Id>, Option<&serde_json::Value>, M, SRC) -> RpcResult<R>,
+    {
+        let context = match self.pipeline.begin_payload::<D, _>(payload, &source) {
+            Ok(context) => context,
+            Err(cached) => return Some(cached),
+        };
+        let processed = {
+            let _enter = context.span().enter();
+            match self
+                .pipeline
+                .prepare::<D, M, R, _>(payload, &source, context.method())
+            {
+                Ok(request) => self.call(request, source, handle).into_processed::<D>(),
+                Err(processed) => processed,
+            }
+        };
+        self.pipeline.finish_payload(context, payload, processed)
+    }
+    /// Pack a response (e.g. produced out of band) with the given data format. If the response
+    /// can not be packed, a server error response with the same id is packed instead
+    pub fn pack_response<D>(&self, response: &Response<R>) -> Option<Vec<u8>>
+    where
+        D: DataFormat,
+    {
+        pack_response::<D, R>(response)
+    }
+}
+
+impl<'a, RPC, M, SRC, R> RpcServer<'a, RPC, M, SRC, R>
+where
+    RPC: RpcServerNotifyingHandler<'a, Method = M, Result = R, Source = SRC>,
+    M: Deserialize<'a> + 'a,
+    R: Serialize + 'a,
+    SRC: fmt::Debug,
+{
+    /// Handle a JSON RPC request from a payload, the handler can emit notifications (see
+    /// [`RpcServerNotifyingHandler`]). Returns the response payload (if any) and the packed
+    /// notifications, which should be sent to the caller before the response
+    pub fn handle_request_payload_with_notifications<D>(
+        &'a self,
+        payload: &'a [u8],
+        source: SRC,
+    ) -> (Option<Vec<u8>>, Vec<Vec<u8>>)
+    where
+        D: DataFormat,
+    {
+        let notifier = Notifier::new();
+        let response = self.handle_payload::<D, _>(payload, source, |_, _, method, source| {
+            self.rpc
+                .handle_call_with_notifier(method, source, &notifier)
+        });
+        let notifications = notifier
+            .into_inner()
+            .into_iter()
+            .filter_map(|notification| match D::pack(&notification) {
+                Ok(v) => Some(v),
+                Err(error) => {
+                    error!(%error, "Failed to serialize notification");
+                    None
+                }
+            })
+            .collect();
+        (response, notifications)
+    }
+}
+
+/// Object-safe server dispatch on packed payloads, allows to store servers with different
+/// handlers as trait objects, e.g. in a `HashMap<String, Box<dyn DynRpcServer>>` router. Payloads
+/// are unpacked with the data format `D` (JSON by default)
+pub trait DynRpcServer<D: DataFormat = crate::dataformat::Json> {
+    /// Handle a request payload (see [`RpcServer::handle_request_payload`]), the call source is
+    /// converted into the server source type
+    fn handle_payload(&self, payload: &[u8], source: &str) -> Option<Vec<u8>>;
+}
+
+impl<'a, RPC, M, SRC, R> RpcServer<'a, RPC, M, SRC, R>
+where
+    RPC: RpcServerRefHandler<'a, Method = M, Result = R, Source = SRC>,
+    M: Deserialize<'a> + 'a,
+    R: Serialize + 'a,
+    SRC: fmt::Debug,
+{
+    /// Handle a JSON RPC request with a borrowed source (see [`RpcServerRefHandler`]), the source
+    /// is never cloned
+    pub fn handle_request_ref(&'a self, request: Request<M>, source: &SRC) -> Option<Response<R>> {
+        let span = debug_span!("rpc_request", source = %SourceFmt(&source), method = field::Empty);
+        let _enter = span.enter();
+        let called = match self.begin_call(request, source) {
+            Ok((call, method)) => {
+                let result = self.rpc.handle_call_ref(method, source);
+                call.finish(result, |v| self.rpc.after_call(v, source))
+            }
+            Err(called) => called,
+        };
+        called.into_response()
+    }
+}
+
+impl<RPC, M, SRC, R, D> DynRpcServer<D> for RpcServer<'_, RPC, M, SRC, R>
+where
+    RPC: for<'b> RpcServerHandler<'b, Method = M, Result = R, Source = SRC>,
+    M: de::DeserializeOwned,
+    R: Serialize,
+    SRC: fmt::Debug + for<'b> From<&'b str>,
+    D: DataFormat,
+{
+    fn handle_payload(&self, payload: &[u8], source: &str) -> Option<Vec<u8>> {
+        // the server lifetime is shortened to the call one (methods are owned)
+        let server: &RpcServer<'_, RPC, M, SRC, R> = self;
+        server.handle_request_payload::<D>(payload, source.into())
+    }
+}
+
+/// Collects server-initiated notifications emitted by a handler during a call
+pub struct Notifier<N> {
+    notifications: Mutex<Vec<Request<N>>>,
+}
+
+impl<N> Notifier<N> {
+    fn new() -> Self {
+        Self {
+            notifications: Mutex::new(Vec::new()),
+        }
+    }
+    /// Emit a notification
+    pub fn notify(&self, method: N) {
+        // a panic in a handler thread does not stop other threads from notifying
+        self.notifications
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .push(Request::from_parts(None, method));
+    }
+    fn into_inner(self) -> Vec<Request<N>> {
+        self.notifications
+            .into_inner()
+            .unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+/// Frame JSON responses (e.g. yielded by [`RpcServer::handle_json_batch_iter`]) as a batch response
+/// array. Returns `None` if there are no responses (e.g. the batch contains notifications only)
+pub fn collect_batch_responses<I>(responses: I) -> Option<Vec<u8>>
+where
+    I: IntoIterator<Item = Option<Vec<u8>>>,
+{
+    let mut buf = vec![b'['];
+    for response in responses.into_iter().flatten() {
+        if buf.len() > 1 {
+            buf.push(b',');
+        }
+        buf.extend(response);
+    }
+    if buf.len() == 1 {
+        return None;
+    }
+    buf.push(b']');
+    Some(buf)
+}
+
+/// Creates an error response for a payload which can not be parsed as a request (`InvalidParams`
+/// if the method is known, `MethodNotFound` otherwise). If the payload is not an object at all
+/// (e.g. empty or malformed), a parse error with null id is returned, so the client always gets a
+/// response
+fn invalid_request_response<'a, D, M, R>(payload: &[u8], error: String) -> Option<Response<R>>
+where
+    D: DataFormat,
+    M: Deserialize<'a>,
+{
+    match D::unpack::<InvalidRequest>(payload) {
+        Ok(invalid) => {
+            // two-stage parse: if the method name is known, the params are invalid
+            match D::unpack::<MethodPeek>(payload).map(|peek| peek.method) {
+                Ok(Some(method)) if is_method_known::<M>(&method) => {
+                    invalid.into_invalid_params_response(error)
+                }
+                Ok(Some(_)) => invalid.into_response(error),
+                // the method is missing, null or not a string
+                Ok(None) | Err(_) => invalid.into_invalid_request_response(error),
+            }
+        }
+        Err(_) => Some(Response::from_handler_response(
+            Id::Null,
+            HandlerResponse::Err(RpcError::new(RpcErrorKind::ParseError, error)),
+        )),
+    }
+}
+
+/// Larger thread-local response buffers are shrunk back after use
+const MAX_RESPONSE_BUF_CAPACITY: usize = 1024 * 1024;
+
+thread_local! {
+    static RESPONSE_BUF: RefCell<Vec<u8>> = const { RefCell::new(Vec::new()) };
+}
+
+fn pack_response<D, R>(response: &Response<R>) -> Option<Vec<u8>>
+where
+    D: DataFormat,
+    R: Serialize,
+{
+    // the buffer keeps its capacity between calls, so the response is serialized without
+    // re-allocations and copied once
+    let packed = RESPONSE_BUF.with(|buf| {
+        let mut buf = buf.borrow_mut();
+        buf.clear();
+        let packed = D::pack_into(response, &mut buf).map(|()| buf.to_vec());
+        if buf.capacity() > MAX_RESPONSE_BUF_CAPACITY {
+            buf.clear();
+            buf.shrink_to(MAX_RESPONSE_BUF_CAPACITY);
+        }
+        packed
+    });
+    match packed {
+        Ok(v) => Some(v),
+        Err(error) => {
+            error!(%error, "Failed to serialize response");
+            D::pack(&Response::<R>::from_server_error(
+                response.id().clone(),
+                error.to_string(),
+            ))
+            .ok()
+        }
+    }
+}
+
+/// Validates that no map in the payload contains duplicate keys
+struct UniqueKeys;
+
+impl<'de> Deserialize<'de> for UniqueKeys {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        deserializer.deserialize_any(UniqueKeysVisitor)
+    }
+}
+
+struct UniqueKeysVisitor;
+
+impl<'de> Visitor<'de> for UniqueKeysVisitor {
+    type Value = UniqueKeys;
+
+    fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        formatter.write_str("any value")
+    }
+    fn visit_bool<E: de::Error>(self, _: bool) -> Result<Self::Value, E> {
+        Ok(UniqueKeys)
+    }
+    fn visit_i64<E: de::Error>(self, _: i64) -> Result<Self::Value, E> {
+        Ok(UniqueKeys)
+    }
+    fn visit_u64<E: de::Error>(self, _: u64) -> Result<Self::Value, E> {
+        Ok(UniqueKeys)
+    }
+    fn visit_f64<E: de::Error>(self, _: f64) -> Result<Self::Value, E> {
+        Ok(UniqueKeys)
+    }
+    fn visit_str<E: de::Error>(self, _: &str) -> Result<Self::Value, E> {
+        Ok(UniqueKeys)
+    }
+    fn visit_bytes<E: de::Error>(self, _: &[u8]) -> Result<Self::Value, E> {
+        Ok(UniqueKeys)
+    }
+    fn visit_none<E: de::Error>(self) -> Result<Self::Value, E> {
+        Ok(UniqueKeys)
+    }
+    fn visit_unit<E: de::Error>(self) -> Result<Self::Value, E> {
+        Ok(UniqueKeys)
+    }
+    fn visit_some<D: Deserializer<'de>>(self, deserializer: D) -> Result<Self::Value, D::Error> {
+        UniqueKeys::deserialize(deserializer)
+    }
+    fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<Self::Value, A::Error> {
+        while seq.next_element::<UniqueKeys>()?.is_some() {}
+        Ok(UniqueKeys)
+    }
+    fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<Self::Value, A::Error> {
+        let mut keys = BTreeSet::new();
+        while let Some(key) = map.next_key::<String>()? {
+            map.next_value::<UniqueKeys>()?;
+            if let Some(key) = keys.replace(key) {
+                return Err(de::Error::custom(format!("duplicate key: {}", key)));
+            }
+        }
+        Ok(UniqueKeys)
+    }
+}
+
+/// RPC server trait
+#[allow(clippy::module_name_repetitions)]
+pub trait RpcServerHandler<'a> {
+    /// Methods to handle
+    type Method: Deserialize<'a>;
+    /// Result of the methods (serialize-only, the server never de-serializes results, so
+    /// borrowed and serialize-only types can be used)
+    type Result: Serialize;
+    /// Source of the call (IP address, connection metadata etc.), must implement `Debug` for
+    /// logging (plain strings are logged unquoted, as with `Display`)
+    type Source;
+
+    /// Called before each call (e.g. for authentication or rate limiting). If an error is
+    /// returned, the call is not handled and the error is sent back to the client instead.
+    #[allow(unused_variables)]
+    fn before_call(&self, method: &Self::Method, source: &Self::Source) -> RpcResult<()> {
+        Ok(())
+    }
+    /// A method to handle calls
+    fn handle_call(&'a self, method: Self::Method, source: Self::Source)
+        -> RpcResult<Self::Result>;
+    /// A method to handle calls with access to the request id (`None` for notifications), e.g. for
+    /// audit logs. Called by [`RpcServer::handle_request`] and [`RpcServer::handle_request_payload`]
+    /// (and so by the batch and [`DynRpcServer`] dispatch built on it), other entry points call
+    /// their own handler methods. The default ignores the id and calls
+    /// [`RpcServerHandler::handle_call`]
+    #[allow(unused_variables)]
+    fn handle_call_with_id(
+        &'a self,
+        id: Option<&Id>,
+        method: Self::Method,
+        source: Self::Source,
+    ) -> RpcResult<Self::Result> {
+        self.handle_call(method, source)
+    }
+    #[cfg(feature = "meta")]
+    /// A method to handle calls with access to the request id and metadata (see
+    /// [`Request::with_meta`]). Called instead of [`RpcServerHandler::handle_call_with_id`] by
+    /// the same entry points, the default ignores the metadata and calls
+    /// [`RpcServerHandler::handle_call_with_id`]
+    #[allow(unused_variables)]
+    fn handle_call_with_meta(
+        &'a self,
+        id: Option<&Id>,
+        meta: Option<&serde_json::Value>,
+        method: Self::Method,
+        source: Self::Source,
+    ) -> RpcResult<Self::Result> {
+        self.handle_call_with_id(id, method, source)
+    }
+    /// Called with each successful call result before it is serialized (e.g. to audit or redact
+    /// the result for certain sources), errors are not passed. As owned sources are moved into
+    /// the handler, the hook is applied to calls with borrowed sources only (see
+    /// [`RpcServer::handle_request_ref`]). The default returns the result as-is
+    #[allow(unused_variables)]
+    fn after_call(&self, result: Self::Result, source: &Self::Source) -> Self::Result {
+        result
+    }
+    /// Returns true if a notification (a request with no id) for the method must still be
+    /// acknowledged. The server assigns a synthetic id (a string `ack-<n>`, which does not clash
+    /// with client counter ids) to such notifications and returns a response, so the client can
+    /// confirm the receipt via a side channel. The default is false.
+    #[allow(unused_variables)]
+    fn ack_required(&self, method: &Self::Method) -> bool {
+        false
+    }
+}
+
+/// RPC server handler which handles calls with a borrowed source (e.g. a heavy connection
+/// context), used with [`RpcServer::handle_request_ref`]
+pub trait RpcServerRefHandler<'a>: RpcServerHandler<'a> {
+    /// A method to handle calls with a borrowed source
+    fn handle_call_ref(
+        &'a self,
+        method: Self::Method,
+        source: &Self::Source,
+    ) -> RpcResult<Self::Result>;
+}
+
+/// RPC server handler which can emit server-initiated notifications (e.g. progress updates) to
+/// the caller, used with [`RpcServer::handle_request_payload_with_notifications`]
+///
+/// The handler defines the notification method type only, the sink is always a server-owned
+/// [`Notifier`], as the server must collect and pack the notifications after the call
+pub trait RpcServerNotifyingHandler<'a>: RpcServerHandler<'a> {
+    /// Notifications emitted by the handler
+    type Notification: Serialize;
+
+    /// A method to handle calls with a notifier. The default implementation calls
+    /// [`RpcServerHandler::handle_call`]
+    #[allow(unused_variables)]
+    fn handle_call_with_notifier(
+        &'a self,
+        method: Self::Method,
+        source: Self::Source,
+        notifier: &Notifier<Self::Notification>,
+    ) -> RpcResult<Self::Result> {
+        self.handle_call(method, source)
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use core::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+    use std::sync::Arc;
+
+    use super::*;
+    use crate::{client::RpcClient, dataformat::Json};
+
+    #[derive(Serialize, Deserialize, Debug)]
+    #[cfg_attr(
+        feature = "canonical",
+        serde(tag = "method", content = "params", deny_unknown_fields)
+    )]
+    #[cfg_attr(
+        not(feature = "canonical"),
+        serde(tag = "m", content = "p", deny_unknown_fields)
+    )]
+    pub(super) enum TestMethod {
+        #[serde(rename = "test")]
+        Test {},
+        #[serde(rename = "ack")]
+        Ack {},
+        #[serde(rename = "add")]
+        Add { a: i64, b: i64 },
+    }
+
+    #[derive(Default)]
+    struct TestRpc {
+        calls: AtomicUsize,
+        rejecting: AtomicBool,
+    }
+
+    impl RpcServerHandler<'_> for TestRpc {
+        type Method = TestMethod;
+        type Result = i64;
+        type Source = &'static str;
+
+        fn handle_call(&self, method: TestMethod, _source: Self::Source) -> RpcResult<i64> {
+            self.calls.fetch_add(1, Ordering::SeqCst);
+            match method {
+                TestMethod::Test {} | TestMethod::Ack {} => Ok(0),
+                TestMethod::Add { a, b } => Ok(a + b),
+            }
+        }
+        fn before_call(&self, _method: &TestMethod, _source: &Self::Source) -> RpcResult<()> {
+            if self.rejecting.load(Ordering::SeqCst) {
+                return Err(RpcError::new(
+                    RpcErrorKind::Custom(-32029),
+                    "too many requests".to_owned(),
+                ));
+            }
+            Ok(())
+        }
+        fn ack_required(&self, method: &TestMethod) -> bool {
+            matches!(method, TestMethod::Ack {})
+        }
+    }
+
+    pub(super) fn client() -> RpcClient<'static, Json, TestMethod, i64> {
+        RpcClient::new()
+    }
+
+    /// A raw request payload in the active layout, fields are given as raw JSON values
+    pub(super) fn raw_request(
+        id: Option<&str>,
+        method: Option<&str>,
+        params: Option<&str>,
+    ) -> Vec<u8> {
+        let mut fields = vec![format!("\"{}\":\"2.0\"", crate::field_names::JSONRPC)];
+        for (name, value) in [
+            (crate::field_names::ID, id),
+            (crate::field_names::METHOD, method),
+            (crate::field_names::PARAMS, params),
+        ] {
+            if let Some(value) = value {
+                fields.push(format!("\"{}\":{}", name, value));
+            }
+        }
+        format!("{{{}}}", fields.join(",")).into_bytes()
+    }
+
+    pub(super) fn unpack_response(payload: &[u8]) -> Response<i64> {
+        Json::unpack(payload).unwrap()
+    }
+
+    #[test]
+    fn test_access_log() {
+        let records = Arc::new(Mutex::new(Vec::new()));
+        let sink_records = records.clone();
+        let server = RpcServer::new(TestRpc::default()).with_access_log(move |record| {
+            sink_records.lock().unwrap().push(record.to_json_line());
+        });
+        let client = client();
+        let req = client.request(TestMethod::Add { a: 1, b: 2 }).unwrap();
+        let response = server
+            .handle_request_payload::<Json>(req.payload(), "peer1")
+            .unwrap();
+        server.handle_request_payload::<Json>(b"{", "peer2");
+        let records = records.lock().unwrap();
+        assert_eq!(records.len(), 2);
+        let first: serde_json::Value = serde_json::from_str(&records[0]).unwrap();
+        assert_eq!(first["source"], "peer1");
+        assert_eq!(first["method"], "add");
+        assert_eq!(first["id"], 0);
+        assert_eq!(first["outcome"], "ok");
+        assert_eq!(first["code"], serde_json::Value::Null);
+        assert_eq!(first["request_bytes"], req.payload().len());
+        assert_eq!(first["response_bytes"], response.len());
+        let second: serde_json::Value = serde_json::from_str(&records[1]).unwrap();
+        assert_eq!(second["source"], "peer2");
+        assert_eq!(second["method"], serde_json::Value::Null);
+        assert_eq!(second["outcome"], "invalid");
+        assert_eq!(second["code"], -32700);
+        assert_eq!(second["request_bytes"], 1);
+    }
+
+    #[test]
+    fn test_source_fmt() {
+        assert_eq!(SourceFmt(&"peer1").to_string(), "peer1");
+        assert_eq!(SourceFmt(&"a\"b".to_owned()).to_string(), r#""a\"b""#);
+        let addr: std::net::SocketAddr = "127.0.0.1:7".parse().unwrap();
+        assert_eq!(SourceFmt(&addr).to_string(), "127.0.0.1:7");
+        assert_eq!(SourceFmt(&Some(7)).to_string(), "Some(7)");
+    }
+
+    #[test]
+    fn test_duplicate_keys_rejected() {
+        let payload = raw_request(Some("1"), Some("\"add\""), Some(r#"{"a":1,"a":2,"b":3}"#));
+        let server = RpcServer::new(TestRpc::default()).with_duplicate_keys_rejected();
+        let response = unpack_response(
+            &server
+                .handle_request_payload::<Json>(&payload, "test")
+                .unwrap(),
+        );
+        assert_eq!(response.id(), &Id::from(1));
+        let err = response.handler_response().err().unwrap();
+        assert_eq!(err.kind(), RpcErrorKind::InvalidRequest);
+        assert!(err.message().unwrap().contains("duplicate key: a"));
+        assert_eq!(server.rpc.calls.load(Ordering::SeqCst), 0);
+    }
+
+    #[test]
+    fn test_pack_deferred_response() {
+        let server = RpcServer::new(TestRpc::default());
+        let packed = server
+            .pack_response::<Json>(&Response::from_handler_response(
+                Id::from(7),
+                HandlerResponse::Ok(42),
+            ))
+            .unwrap();
+        let response = unpack_response(&packed);
+        assert_eq!(response.id(), &Id::from(7));
+        assert_eq!(response.handler_response().ok(), Some(&42));
+        let packed = server
+            .pack_response::<Json>(&Response::error(Id::from(8), RpcError::INVALID_PARAMS))
+            .unwrap();
+        let response = unpack_response(&packed);
+        assert_eq!(response.id(), &Id::from(8));
+        assert_eq!(
+            response.handler_response().err(),
+            Some(&RpcError::INVALID_PARAMS)
+        );
+    }
+
+    #[test]
+    fn test_ack_required_notification() {
+        let server = RpcServer::new(TestRpc::default());
+        let client = client();
+        let notification = client.request0(TestMethod::Ack {}).unwrap();
+        let response = server
+            .handle_request_payload::<Json>(notification.payload(), "test")
+            .expect("no ack response");
+        let response = Json::unpack::<Response<i64>>(&response).unwrap();
+        assert_eq!(response.id(), &Id::from("ack-0"));
+        assert_eq!(response.handler_response().ok(), Some(&0));
+        // regular notifications are not acknowledged
+        let notification = client.request0(TestMethod::Test {}).unwrap();
+        assert!(server
+            .handle_request_payload::<Json>(notification.payload(), "test")
+            .is_none());
+        assert_eq!(server.rpc.calls.load(Ordering::SeqCst), 2);
+    }
+
+    #[test]
+    fn test_method_filter() {
+        let server =
+            RpcServer::new(TestRpc::default()).with_method_filter(|method| method != "add");
+        let client = client();
+        let req = client.request(TestMethod::Test {}).unwrap();
+        let response = unpack_response(
+            &server
+                .handle_request_payload::<Json>(req.payload(), "test")
+                .unwrap(),
+        );
+        assert_eq!(response.handler_response().ok(), Some(&0));
+        let req = client.request(TestMethod::Add { a: 1, b: 2 }).unwrap();
+        let response = unpack_response(
+            &server
+                .handle_request_payload::<Json>(req.payload(), "test")
+                .unwrap(),
+        );
+        assert_eq!(response.id(), &Id::from(1));
+        assert_eq!(
+            response.handler_response().err().unwrap().kind(),
+            RpcErrorKind::MethodNotFound
+        );
+        // the method name is not a string
+        let payload = raw_request(Some("2"), Some("1"), None);
+        let response = unpack_response(
+            &server
+                .handle_request_payload::<Json>(&payload, "test")
+                .unwrap(),
+        );
+        assert_eq!(response.id(), &Id::from(2));
+        assert_eq!(
+            response.handler_response().err().unwrap().kind(),
+            RpcErrorKind::InvalidRequest
+        );
+        assert_eq!(server.rpc.calls.load(Ordering::SeqCst), 1);
+    }
+
+    #[test]
+    fn test_empty_and_malformed_payloads() {
+        let server = RpcServer::new(TestRpc::default());
+        for payload in [&b""[..], b"   ", b"{"] {
+            let response = unpack_response(
+                &server
+                    .handle_request_payload::<Json>(payload, "test")
+                    .expect("no response"),
+            );
+            assert_eq!(response.id(), &Id::Null);
+            assert_eq!(
+                response.handler_response().err().unwrap().kind(),
+                RpcErrorKind::ParseError
+            );
+        }
+    }
+
+    #[test]
+    fn test_missing_method() {
+        let server = RpcServer::new(TestRpc::default());
+        for method in [None, Some("null"), Some("7")] {
+            let payload = raw_request(Some("1"), method, None);
+            let response = unpack_response(
+                &server
+                    .handle_request_payload::<Json>(&payload, "test")
+                    .expect("no response"),
+            );
+            assert_eq!(response.id(), &Id::from(1));
+            assert_eq!(
+                response.handler_response().err().unwrap().kind(),
+                RpcErrorKind::InvalidRequest
+            );
+        }
+        assert_eq!(server.rpc.calls.load(Ordering::SeqCst), 0);
+    }
+
+    #[test]
+    fn test_invalid_request_id() {
+        let server = RpcServer::new(TestRpc::default());
+        for id in ["5", "\"x\""] {
+            let payload = raw_request(Some(id), Some("\"unknown\""), Some("{}"));
+            let response = unpack_response(
+                &server
+                    .handle_request_payload::<Json>(&payload, "test")
+                    .expect("no response"),
+            );
+            assert_eq!(response.id(), &serde_json::from_str::<Id>(id).unwrap());
+            assert_eq!(
+                response.handler_response().err().unwrap().kind(),
+                RpcErrorKind::MethodNotFound
+            );
+        }
+    }
+
+    #[test]
+    fn test_json_batch_iter() {
+        let client = client();
+        let batch = client
+            .batch([TestMethod::Test {}, TestMethod::Add { a: 1, b: 2 }])
+            .unwrap();
+        let server = RpcServer::new(TestRpc::default());
+        let responses: Vec<Response<i64>> = server
+            .handle_json_batch_iter(batch.payload(), "test")
+            .map(|response| unpack_response(&response.unwrap()))
+            .collect();
+        assert_eq!(responses.len(), 2);
+        assert_eq!(responses[1].handler_response().ok(), Some(&3));
+        // each request fits the limit, the batch does not
+        let max_len = serde_json::from_slice::<Vec<&serde_json::value::RawValue>>(batch.payload())
+            .unwrap()
+            .iter()
+            .map(|raw| raw.get().len())
+            .max()
+            .unwrap();
+        assert!(batch.payload().len() > max_len);
+        let server = RpcServer::new(TestRpc::default()).with_max_payload_len(max_len);
+        let mut responses = server.handle_json_batch_iter(batch.payload(), "test");
+        let response = unpack_response(&responses.next().unwrap().unwrap());
+        assert!(responses.next().is_none());
+        assert_eq!(response.id(), &Id::Null);
+        assert_eq!(
+            response.handler_response().err().unwrap().kind(),
+            RpcErrorKind::InvalidRequest
+        );
+        assert_eq!(server.rpc.calls.load(Ordering::SeqCst), 0);
+    }
+
+    #[test]
+    fn test_json_batch_iter_empty() {
+        let server = RpcServer::new(TestRpc::default());
+        let mut responses = server.handle_json_batch_iter(b" [ ] ", "test");
+        let response = unpack_response(&responses.next().unwrap().unwrap());
+        assert!(responses.next().is_none());
+        assert_eq!(response.id(), &Id::Null);
+        assert_eq!(
+            response.handler_response().err().unwrap().kind(),
+            RpcErrorKind::InvalidRequest
+        );
+    }
+
+    #[test]
+    fn test_notify_batch() {
+        let payload = client()
+            .notify_batch([TestMethod::Test {}, TestMethod::Add { a: 1, b: 2 }])
+            .unwrap();
+        let server = RpcServer::new(TestRpc::default());
+        assert!(collect_batch_responses(server.handle_json_batch_iter(&payload, "test")).is_none());
+        assert_eq!(server.rpc.calls.load(Ordering::SeqCst), 2);
+    }
+
+    #[test]
+    fn test_max_payload_len() {
+        // the params are invalid, a fully parsed payload would get InvalidParams
+        let payload = raw_request(Some("1"), Some("\"add\""), Some(r#"{"a":"x","b":2}"#));
+        let server = RpcServer::new(TestRpc::default()).with_max_payload_len(payload.len() - 1);
+        let response = unpack_response(
+            &server
+                .handle_request_payload::<Json>(&payload, "test")
+                .unwrap(),
+        );
+        assert_eq!(response.id(), &Id::from(1));
+        let err = response.handler_response().err().unwrap();
+        assert_eq!(err.kind(), RpcErrorKind::InvalidRequest);
+        assert_eq!(err.message(), Some("request payload is too large"));
+        let server = RpcServer::new(TestRpc::default()).with_max_payload_len(payload.len());
+        let response = unpack_response(
+            &server
+                .handle_request_payload::<Json>(&payload, "test")
+                .unwrap(),
+        );
+        assert_eq!(
+            response.handler_response().err().unwrap().kind(),
+            RpcErrorKind::InvalidParams
+        );
+        assert_eq!(server.rpc.calls.load(Ordering::SeqCst), 0);
+    }
+
+    #[test]
+    fn test_unknown_params_invalid() {
+        let payload = raw_request(Some("1"), Some("\"test\""), Some(r#"{"abc":123}"#));
+        let server = RpcServer::new(TestRpc::default());
+        let response = unpack_response(
+            &server
+                .handle_request_payload::<Json>(&payload, "test")
+                .unwrap(),
+        );
+        assert_eq!(response.id(), &Id::from(1));
+        assert_eq!(
+            i32::from(response.handler_response().err().unwrap().kind()),
+            -32602
+        );
+        // unknown methods are still reported as such
+        let payload = raw_request(Some("2"), Some("\"nope\""), Some(r#"{"abc":123}"#));
+        let response = unpack_response(
+            &server
+                .handle_request_payload::<Json>(&payload, "test")
+                .unwrap(),
+        );
+        assert_eq!(
+            response.handler_response().err().unwrap().kind(),
+            RpcErrorKind::MethodNotFound
+        );
+    }
+
+    #[test]
+    fn test_dedup_cache() {
+        let server = RpcServer::new(TestRpc::default()).with_dedup_cache(1);
+        let client = client();
+        let first = client.request(TestMethod::Add { a: 1, b: 2 }).unwrap();
+        let call = |payload: &[u8], source| {
+            unpack_response(
+                &server
+                    .handle_request_payload::<Json>(payload, source)
+                    .unwrap(),
+            )
+        };
+        // rejected calls are not cached
+        server.rpc.rejecting.store(true, Ordering::SeqCst);
+        assert!(call(first.payload(), "peer1").handler_response().is_err());
+        server.rpc.rejecting.store(false, Ordering::SeqCst);
+        assert_eq!(
+            call(first.payload(), "peer1").handler_response().ok(),
+            Some(&3)
+        );
+        assert_eq!(server.rpc.calls.load(Ordering::SeqCst), 1);
+        // hit
+        assert_eq!(
+            call(first.payload(), "peer1").handler_response().ok(),
+            Some(&3)
+        );
+        assert_eq!(server.rpc.calls.load(Ordering::SeqCst), 1);
+        // the same id from another source is a miss, the first entry is evicted
+        assert_eq!(
+            call(first.payload(), "peer2").handler_response().ok(),
+            Some(&3)
+        );
+        assert_eq!(server.rpc.calls.load(Ordering::SeqCst), 2);
+        assert_eq!(
+            call(first.payload(), "peer1").handler_response().ok(),
+            Some(&3)
+        );
+        assert_eq!(server.rpc.calls.load(Ordering::SeqCst), 3);
+    }
+
+    /// A connection context which can not be cloned
+    #[derive(Debug)]
+    struct Conn {
+        scale: i64,
+    }
+
+    struct ConnRpc;
+
+    impl RpcServerHandler<'_> for ConnRpc {
+        type Method = TestMethod;
+        type Result = i64;
+        type Source = Conn;
+
+        fn handle_call(&self, method: TestMethod, source: Conn) -> RpcResult<i64> {
+            self.handle_call_ref(method, &source)
+        }
+        fn after_call(&self, result: i64, source: &Conn) -> i64 {
+            result * source.scale
+        }
+    }
+
+    impl RpcServerRefHandler<'_> for ConnRpc {
+        fn handle_call_ref(&self, method: TestMethod, _source: &Conn) -> RpcResult<i64> {
+            match method {
+                TestMethod::Test {} | TestMethod::Ack {} => Ok(0),
+                TestMethod::Add { a, b } => Ok(a + b),
+            }
+        }
+    }
+
+    #[test]
+    fn test_non_clone_source() {
+        let server = RpcServer::new(ConnRpc);
+        let req = client().request(TestMethod::Add { a: 1, b: 2 }).unwrap();
+        // owned sources are moved into the handler, `after_call` is not applied
+        let response = server
+            .handle_request_payload::<Json>(req.payload(), Conn { scale: 10 })
+            .unwrap();
+        assert_eq!(unpack_response(&response).handler_response().ok(), Some(&3));
+        let request = Request::from_parts(Some(1.into()), TestMethod::Add { a: 1, b: 2 });
+        let response = server
+            .handle_request_ref(request, &Conn { scale: 10 })
+            .unwrap();
+        assert_eq!(response.handler_response().ok(), Some(&30));
+    }
+}
@@ -0,0 +1,349 @@
+use core::{
+    fmt,
+    sync::atomic::{AtomicU32, Ordering},
+};
+use std::time::Instant;
+use tracing::{debug, debug_span, error, field, Span};
+
+use serde::{Deserialize, Serialize};
+
+use super::{
+    dedup::{DedupCache, DedupKey},
+    invalid_request_response, pack_response, AccessLogOutcome, AccessLogRecord, SourceFmt,
+    UniqueKeys, ERR_FAILED_TO_PARSE,
+};
+use crate::{
+    dataformat::DataFormat,
+    request::{IdPeek, InvalidRequest, MethodPeek, Request},
+    response::{HandlerResponse, Response},
+    Id, RpcError, RpcErrorKind, RpcResult,
+};
+
+pub(super) type AccessLogSink = Box<dyn Fn(&AccessLogRecord) + Send + Sync>;
+pub(super) type MethodFilter = Box<dyn Fn(&str) -> bool + Send + Sync>;
+
+/// Server options and request processing steps shared by the sync and async servers, so both
+/// apply the same checks and hooks. Handler calls are made by the servers between
+/// [`Pipeline::begin_call`] and [`Call::finish`]
+#[derive(Default)]
+pub(super) struct Pipeline {
+    ack_id: AtomicU32,
+    pub(super) access_log: Option<AccessLogSink>,
+    pub(super) reject_duplicate_keys: bool,
+    pub(super) method_filter: Option<MethodFilter>,
+    pub(super) max_payload_len: Option<usize>,
+    pub(super) dedup_cache: Option<DedupCache>,
+}
+
+/// A processed request payload
+pub(super) struct Processed {
+    payload: Option<Vec<u8>>,
+    id: Option<Id>,
+    outcome: AccessLogOutcome,
+    code: Option<RpcErrorKind>,
+    // the handler has been called (only such responses are cached)
+    handled: bool,
+}
+
+/// A request payload being processed
+pub(super) struct PayloadContext {
+    span: Span,
+    method: Option<String>,
+    source: Option<String>,
+    dedup_key: Option<DedupKey>,
+    started: Instant,
+}
+
+impl PayloadContext {
+    pub(super) fn span(&self) -> &Span {
+        &self.span
+    }
+    pub(super) fn method(&self) -> Option<&str> {
+        self.method.as_deref()
+    }
+}
+
+/// A call accepted for handling
+pub(super) struct Call {
+    pub(super) id: Option<Id>,
+    pub(super) meta: Option<serde_json::Value>,
+    started: Instant,
+}
+
+/// A call result
+pub(super) struct Called<R> {
+    id: Option<Id>,
+    result: HandlerResponse<R>,
+    handled: bool,
+}
+
+impl Pipeline {
+    pub(super) fn is_oversized(&self, payload: &[u8]) -> bool {
+        matches!(self.max_payload_len, Some(max) if payload.len() > max)
+    }
+    /// Start processing a request payload, returns the cached response if the request has been
+    /// already handled (see [`DedupCache`])
+    pub(super) fn begin_payload<D, SRC>(
+        &self,
+        payload: &[u8],
+        source: &SRC,
+    ) -> Result<PayloadContext, Vec<u8>>
+    where
+        D: DataFormat,
+        SRC: fmt::Debug,
+    {
+        let started = Instant::now();
+        let dedup_key = if self.dedup_cache.is_none() || self.is_oversized(payload) {
+            None
+        } else {
+            D::unpack::<IdPeek>(payload)
+                .ok()
+                .and_then(|peek| peek.id)
+                .map(|id| (SourceFmt(source).to_string(), id.to_string()))
+        };
+        if let (Some(cache), Some(key)) = (&self.dedup_cache, &dedup_key) {
+            if let Some(response) = cache.get(key) {
+                debug!(source = %SourceFmt(source), "RPC response served from the deduplication cache");
+                return Err(response);
+            }
+        }
+        let span = debug_span!("rpc_request", source = %SourceFmt(source), method = field::Empty);
+        let method: Option<String> =
+            if (self.access_log.is_some() || self.method_filter.is_some() || !span.is_disabled())
+                && !self.is_oversized(payload)
+            {
+                D::unpack::<MethodPeek>(payload)
+                    .ok()
+                    .and_then(|peek| peek.method.map(Into::into))
+            } else {
+                None
+            };
+        if let Some(method) = &method {
+            span.record("method", method.as_str());
+        }
+        Ok(PayloadContext {
+            span,
+            method,
+            source: self
+                .access_log
+                .is_some()
+                .then(|| SourceFmt(source).to_string()),
+            dedup_key,
+            started,
+        })
+    }
+    /// Finish processing a request payload: emit the access log record and cache the response
+    pub(super) fn finish_payload(
+        &self,
+        context: PayloadContext,
+        payload: &[u8],
+        processed: Processed,
+    ) -> Option<Vec<u8>> {
+        if processed.handled {
+            if let (Some(cache), Some(key), Some(response)) =
+                (&self.dedup_cache, context.dedup_key, &processed.payload)
+            {
+                cache.insert(key, response.clone());
+            }
+        }
+        if let Some(access_log) = &self.access_log {
+            access_log(&AccessLogRecord {
+                source: context.source.unwrap_or_default(),
+                method: context.method,
+                id: processed.id,
+                outcome: processed.outcome,
+                code: processed.code,
+                duration_us: u64::try_from(context.started.elapsed().as_micros())
+                    .unwrap_or(u64::MAX),
+                request_bytes: payload.len(),
+                response_bytes: processed.payload.as_ref().map_or(0, Vec::len),
+            });
+        }
+        processed.payload
+    }
+    /// Check and unpack a request payload, the error contains the response to invalid requests
+    pub(super) fn prepare<'a, D, M, R, SRC>(
+        &self,
+        payload: &'a [u8],
+        source: &SRC,
+        method: Option<&str>,
+    ) -> Result<Request<M>, Processed>
+    where
+        D: DataFormat,
+        M: Deserialize<'a>,
+        R: Serialize,
+        SRC: fmt::Debug,
+    {
+        let invalid = |response: Option<Response<R>>| Processed {
+            id: response.as_ref().map(|r| r.id().clone()),
+            code: response
+                .as_ref()
+                .and_then(|r| r.handler_response().err().map(RpcError::kind)),
+            payload: response.and_then(|response| pack_response::<D, R>(&response)),
+            outcome: AccessLogOutcome::Invalid,
+            handled: false,
+        };
+        if self.is_oversized(payload) {
+            error!(
+                source = %SourceFmt(source),
+                len = payload.len(),
+                "RPC request payload is too large"
+            );
+            // the id is read only, params are not de-serialized
+            return Err(invalid(
+                D::unpack::<InvalidRequest>(payload)
+                    .ok()
+                    .and_then(|invalid| invalid.id().cloned())
+                    .map(|id| {
+                        Response::error(
+                            id,
+                            RpcError::new(
+                                RpcErrorKind::InvalidRequest,
+                                "request payload is too large".to_owned(),
+                            ),
+                        )
+                    }),
+            ));
+        }
+        if self.reject_duplicate_keys {
+            if let Err(error) = D::unpack::<UniqueKeys>(payload) {
+                error!(source = %SourceFmt(source), %error, ERR_FAILED_TO_PARSE);
+                return Err(invalid(
+                    D::unpack::<InvalidRequest>(payload)
+                        .ok()
+                        .and_then(|invalid| invalid.id().cloned())
+                        .map(|id| {
+                            Response::error(
+                                id,
+                                RpcError::new(RpcErrorKind::InvalidRequest, error.to_string()),
+                            )
+                        }),
+                ));
+            }
+        }
+        if let Some(filter) = &self.method_filter {
+            // the filter fails closed: requests with no readable method name are rejected
+            let Some(method) = method else {
+                error!(source = %SourceFmt(source), "RPC request method name can not be read");
+                return Err(invalid(invalid_request_response::<D, M, R>(
+                    payload,
+                    "the method name can not be read".to_owned(),
+                )));
+            };
+            if !filter(method) {
+                let id = D::unpack::<InvalidRequest>(payload)
+                    .ok()
+                    .and_then(|invalid| invalid.id().cloned());
+                return Err(Processed {
+                    payload: id.clone().and_then(|id| {
+                        pack_response::<D, R>(&Response::error(id, RpcError::METHOD_NOT_FOUND))
+                    }),
+                    id,
+                    outcome: AccessLogOutcome::Error,
+                    code: Some(RpcErrorKind::MethodNotFound),
+                    handled: false,
+                });
+            }
+        }
+        D::unpack::<Request<M>>(payload).map_err(|error| {
+            error!(source = %SourceFmt(source), %error, ERR_FAILED_TO_PARSE);
+            invalid(invalid_request_response::<D, M, R>(
+                payload,
+                error.to_string(),
+            ))
+        })
+    }
+    /// Start a call: assign a synthetic id to notifications which must be acknowledged and run
+    /// the pre-call hook. The error contains the result of calls rejected by the hook
+    pub(super) fn begin_call<M, R>(
+        &self,
+        request: Request<M>,
+        ack_required: impl FnOnce(&M) -> bool,
+        before_call: impl FnOnce(&M) -> RpcResult<()>,
+    ) -> Result<(Call, M), Called<R>> {
+        let id = request.id.or_else(|| {
+            ack_required(&request.method).then(|| {
+                // a separate id space, so acks can not be confused with responses to client
+                // counter ids
+                format!("ack-{}", self.ack_id.fetch_add(1, Ordering::SeqCst)).into()
+            })
+        });
+        if let Err(e) = before_call(&request.method) {
+            return Err(Called {
+                id,
+                result: HandlerResponse::Err(e),
+                handled: false,
+            });
+        }
+        #[cfg(feature = "meta")]
+        let meta = request.meta;
+        #[cfg(not(feature = "meta"))]
+        let meta = None;
+        Ok((
+            Call {
+                id,
+                meta,
+                started: Instant::now(),
+            },
+            request.method,
+        ))
+    }
+}
+
+impl Call {
+    /// Finish the call: run the post-call hook for successful results
+    pub(super) fn finish<R>(
+        self,
+        result: RpcResult<R>,
+        after_call: impl FnOnce(R) -> R,
+    ) -> Called<R> {
+        let result = match result {
+            Ok(v) => HandlerResponse::Ok(after_call(v)),
+            Err(e) => HandlerResponse::Err(e),
+        };
+        debug!(
+            elapsed_us = u64::try_from(self.started.elapsed().as_micros()).unwrap_or(u64::MAX),
+            outcome = if result.is_ok() { "ok" } else { "err" },
+            "RPC call handled"
+        );
+        Called {
+            id: self.id,
+            result,
+            handled: true,
+        }
+    }
+}
+
+impl<R> Called<R>
+where
+    R: Serialize,
+{
+    /// Mark the call as not handled, so its response is not cached (e.g. if it has been
+    /// cancelled)
+    #[cfg(feature = "async")]
+    pub(super) fn into_unhandled(mut self) -> Self {
+        self.handled = false;
+        self
+    }
+    pub(super) fn into_response(self) -> Option<Response<R>> {
+        let result = self.result;
+        self.id
+            .map(move |id| Response::from_handler_response(id, result))
+    }
+    pub(super) fn into_processed<D: DataFormat>(self) -> Processed {
+        let code = self.result.err().map(RpcError::kind);
+        Processed {
+            payload: self.id.clone().and_then(|id| {
+                pack_response::<D, R>(&Response::from_handler_response(id, self.result))
+            }),
+            id: self.id,
+            outcome: if code.is_some() {
+                AccessLogOutcome::Error
+            } else {
+                AccessLogOutcome::Ok
+            },
+            code,
+            handled: self.handled,
+        }
+    }
+}
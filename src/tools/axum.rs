@@ -0,0 +1,61 @@
+use ::axum::{
+    async_trait,
+    body::Bytes,
+    extract::{FromRequest, Request as AxumRequest},
+    response::{IntoResponse, Response as AxumResponse},
+};
+use serde::{de::DeserializeOwned, Serialize};
+
+use super::http::HttpResponse;
+use crate::{
+    request::Request,
+    response::{HandlerResponse, Response},
+    Id, RpcError, RpcErrorKind,
+};
+
+/// Axum extractor, reads the request body and de-serializes it into a JSON-RPC request. If the
+/// body can not be read or parsed, a JSON-RPC parse error is returned as [`HttpResponse`]
+pub struct RpcBody<M>(pub Request<M>);
+
+impl<M> RpcBody<M> {
+    /// Get the inner request
+    pub fn into_inner(self) -> Request<M> {
+        self.0
+    }
+}
+
+#[async_trait]
+impl<M, S> FromRequest<S> for RpcBody<M>
+where
+    M: DeserializeOwned + Serialize,
+    S: Send + Sync,
+{
+    type Rejection = AxumResponse;
+
+    async fn from_request(req: AxumRequest, state: &S) -> Result<Self, Self::Rejection> {
+        let body = Bytes::from_request(req, state)
+            .await
+            .map_err(IntoResponse::into_response)?;
+        serde_json::from_slice(&body)
+            .map(RpcBody)
+            .map_err(|e| parse_error_response(&e.to_string()))
+    }
+}
+
+fn parse_error_response(message: &str) -> AxumResponse {
+    let response = Response::<()>::from_parts(
+        Id::Null,
+        HandlerResponse::Err(RpcError::new(RpcErrorKind::ParseError, message.to_owned())),
+    );
+    match HttpResponse::try_from(response) {
+        Ok(r) => r.into_response(),
+        Err(e) => (http::StatusCode::INTERNAL_SERVER_ERROR, e.to_string()).into_response(),
+    }
+}
+
+impl IntoResponse for HttpResponse {
+    fn into_response(self) -> AxumResponse {
+        let (status, headers, body) = self.into_parts();
+        (status, headers, body).into_response()
+    }
+}
@@ -0,0 +1,150 @@
+use core::{fmt, marker::PhantomData};
+
+use serde::Serialize;
+
+use crate::dataformat::DataFormat;
+
+const LENGTH_SIZE: usize = 4;
+
+/// The default maximum frame payload length of [`LengthDelimited`] (8 MiB)
+pub const DEFAULT_MAX_FRAME_LEN: usize = 8 * 1024 * 1024;
+
+/// Sans-io length-delimited codec: each frame is prefixed with its payload length (4 bytes,
+/// big-endian), the payload is packed with the data format `D`. Can be used to implement
+/// framework codecs (e.g. `tokio-util` `Decoder`/`Encoder`).
+///
+/// Frames with payloads longer than `MAX_FRAME_LEN` bytes are rejected by both
+/// [`LengthDelimited::encode`] and [`LengthDelimited::decode`], e.g.
+/// `LengthDelimited<Json, 65536>` for 64 KiB frames.
+pub struct LengthDelimited<D, const MAX_FRAME_LEN: usize = DEFAULT_MAX_FRAME_LEN> {
+    _phantom_d: PhantomData<D>,
+}
+
+impl<D, const MAX_FRAME_LEN: usize> LengthDelimited<D, MAX_FRAME_LEN>
+where
+    D: DataFormat,
+{
+    /// Pack the message and append it as a frame to the buffer
+    pub fn encode(
+        msg: &impl Serialize,
+        dst: &mut Vec<u8>,
+    ) -> Result<(), EncodeError<D::PackError>> {
+        let start = dst.len();
+        dst.extend_from_slice(&[0; LENGTH_SIZE]);
+        if let Err(e) = D::pack_into(msg, dst) {
+            dst.truncate(start);
+            return Err(EncodeError::Pack(e));
+        }
+        let len = dst.len() - start - LENGTH_SIZE;
+        let Some(len) = u32::try_from(len).ok().filter(|_| len <= MAX_FRAME_LEN) else {
+            dst.truncate(start);
+            return Err(EncodeError::TooLarge);
+        };
+        dst[start..start + LENGTH_SIZE].copy_from_slice(&len.to_be_bytes());
+        Ok(())
+    }
+    /// Extract the payload of the first complete frame from the buffer (the frame is removed).
+    /// Returns `None` if the buffer does not contain a complete frame yet. If the frame length
+    /// exceeds `MAX_FRAME_LEN`, the buffer is left as-is and an error is returned (the stream
+    /// can not be recovered and should be closed)
+    pub fn decode(src: &mut Vec<u8>) -> Result<Option<Vec<u8>>, DecodeError> {
+        let Some(len_bytes) = src.get(..LENGTH_SIZE) else {
+            return Ok(None);
+        };
+        let mut len = [0; LENGTH_SIZE];
+        len.copy_from_slice(len_bytes);
+        let len = u32::from_be_bytes(len);
+        let len = usize::try_from(len)
+            .ok()
+            .filter(|len| *len <= MAX_FRAME_LEN)
+            .ok_or(DecodeError::TooLarge(u64::from(len)))?;
+        if src.len() - LENGTH_SIZE < len {
+            return Ok(None);
+        }
+        let payload = src[LENGTH_SIZE..LENGTH_SIZE + len].to_vec();
+        src.drain(..LENGTH_SIZE + len);
+        Ok(Some(payload))
+    }
+}
+
+/// Frame encoding error
+#[derive(Debug)]
+pub enum EncodeError<E> {
+    /// The message can not be packed
+    Pack(E),
+    /// The packed message exceeds the maximum frame length
+    TooLarge,
+}
+
+impl<E: fmt::Display> fmt::Display for EncodeError<E> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            EncodeError::Pack(e) => write!(f, "frame pack error: {}", e),
+            EncodeError::TooLarge => write!(f, "frame is too large"),
+        }
+    }
+}
+
+impl<E: fmt::Debug + fmt::Display> std::error::Error for EncodeError<E> {}
+
+/// Frame decoding error
+#[derive(Debug)]
+pub enum DecodeError {
+    /// The frame length exceeds the maximum (contains the frame length)
+    TooLarge(u64),
+}
+
+impl fmt::Display for DecodeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DecodeError::TooLarge(len) => write!(f, "frame length {} exceeds the maximum", len),
+        }
+    }
+}
+
+impl std::error::Error for DecodeError {}
+
+#[cfg(test)]
+mod tests {
+    use super::{DecodeError, EncodeError, LengthDelimited};
+    use crate::dataformat::Json;
+
+    #[test]
+    fn test_frames() {
+        let mut buf = Vec::new();
+        LengthDelimited::<Json>::encode(&"hello", &mut buf).unwrap();
+        LengthDelimited::<Json>::encode(&[1, 2, 3], &mut buf).unwrap();
+        assert_eq!(&buf[..4], &[0, 0, 0, 7]);
+        let mut partial = buf[..10].to_vec();
+        assert_eq!(LengthDelimited::<Json>::decode(&mut partial).unwrap(), None);
+        assert_eq!(partial.len(), 10);
+        assert_eq!(
+            LengthDelimited::<Json>::decode(&mut buf).unwrap().unwrap(),
+            br#""hello""#
+        );
+        assert_eq!(
+            LengthDelimited::<Json>::decode(&mut buf).unwrap().unwrap(),
+            b"[1,2,3]"
+        );
+        assert!(buf.is_empty());
+        assert_eq!(LengthDelimited::<Json>::decode(&mut buf).unwrap(), None);
+    }
+
+    #[test]
+    fn test_max_frame_len() {
+        let mut buf = Vec::new();
+        assert!(matches!(
+            LengthDelimited::<Json, 4>::encode(&"hello", &mut buf),
+            Err(EncodeError::TooLarge)
+        ));
+        assert!(buf.is_empty());
+        LengthDelimited::<Json>::encode(&"hello", &mut buf).unwrap();
+        // the length is checked before the frame is complete
+        let mut partial = buf[..4].to_vec();
+        assert!(matches!(
+            LengthDelimited::<Json, 4>::decode(&mut partial),
+            Err(DecodeError::TooLarge(7))
+        ));
+        assert_eq!(partial.len(), 4);
+    }
+}
@@ -0,0 +1,48 @@
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::{
+    dataformat::{DataFormat, Json},
+    request::Request,
+    response::Response,
+    Id, RpcResult,
+};
+
+/// A method which is known at runtime only, uses the active layout field names
+#[derive(Serialize, Deserialize)]
+struct DynamicMethod {
+    #[cfg_attr(feature = "canonical", serde(rename = "method"))]
+    #[cfg_attr(not(feature = "canonical"), serde(rename = "m"))]
+    method: String,
+    #[cfg_attr(
+        feature = "canonical",
+        serde(rename = "params", default, skip_serializing_if = "Value::is_null")
+    )]
+    #[cfg_attr(
+        not(feature = "canonical"),
+        serde(rename = "p", default, skip_serializing_if = "Value::is_null")
+    )]
+    params: Value,
+}
+
+/// Build a JSON request payload for the method name and params (omitted if null). If no id is
+/// given, a notification is built
+pub fn build_request(
+    method: &str,
+    params: Value,
+    id: Option<Id>,
+) -> Result<Vec<u8>, serde_json::Error> {
+    Json::pack(&Request::from_parts(
+        id,
+        DynamicMethod {
+            method: method.to_owned(),
+            params,
+        },
+    ))
+}
+
+/// Parse a JSON response payload into the id and the call result
+pub fn parse_response(payload: &[u8]) -> Result<(Id, RpcResult<Value>), serde_json::Error> {
+    let (id, result) = Json::unpack::<Response<Value>>(payload)?.into_parts();
+    Ok((id, result.into()))
+}
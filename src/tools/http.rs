@@ -1,5 +1,5 @@
-use core::fmt;
-use std::collections::BTreeMap;
+use core::fmt::{self, Write as _};
+use std::collections::{btree_map::Entry, BTreeMap};
 
 use http::{header, StatusCode};
 use serde::{de::DeserializeOwned, Serialize};
@@ -16,12 +16,26 @@ pub enum Error {
     InvalidData(String),
 }
 
-use crate::{request::Request, response::Response};
+use crate::{
+    dataformat::{self, DataFormat},
+    request::Request,
+    response::{HandlerResponse, Response},
+    server::{RpcServer, RpcServerHandler},
+    Id, RpcErrorKind,
+};
 
 /// Query string representation of a JSON-RPC request,
 /// as: `i=1&m=method&param1=value1&param2=value2`, where id is optional
 ///
 /// Booleans ("true"/"false"), numbers and "null" are parsed automatically,
+///
+/// Repeated params are collected into arrays (`n=1&n=2` is parsed as `"n": [1, 2]`) and arrays
+/// of scalars are serialized as repeated params. Note that a single-element array is parsed back
+/// as a scalar. Nested objects and arrays are not supported ([`Error::InvalidData`] is returned).
+///
+/// Positional (array) params are serialized with index names (`m=add&0=1&1=2`) and parsed back
+/// into an array if all param names are indices. For positional params, use tuple variants in
+/// the method enum, e.g. `Add(i64, i64)`.
 #[derive(Debug)]
 pub struct QueryString(String);
 
@@ -83,13 +97,26 @@ fn parse_string(s: &str) -> Value {
         Value::Number(n.into())
     } else if let Ok(n) = s.parse::<i64>() {
         Value::Number(n.into())
-    } else if let Ok(n) = s.parse::<f64>() {
-        Value::Number(serde_json::value::Number::from_f64(n).unwrap())
+    } else if cfg!(feature = "preserve-big-int") && is_integer(s) {
+        // the integer does not fit u64/i64, keep it as-is instead of losing precision
+        Value::String(s.to_string())
+    } else if let Some(n) = s
+        .parse::<f64>()
+        .ok()
+        .and_then(serde_json::value::Number::from_f64)
+    {
+        Value::Number(n)
     } else {
+        // not a number, or NaN/infinity (e.g. 1e999), which are not valid JSON numbers
         Value::String(s.to_string())
     }
 }
 
+fn is_integer(s: &str) -> bool {
+    let digits = s.strip_prefix('-').unwrap_or(s);
+    !digits.is_empty() && digits.bytes().all(|b| b.is_ascii_digit())
+}
+
 fn request_from_query_string<M: DeserializeOwned + Serialize>(
     qs: &str,
 ) -> Result<Request<M>, Error> {
@@ -108,11 +135,21 @@ fn request_from_query_string<M: DeserializeOwned + Serialize>(
                 method = Some(value.to_string());
             }
             n => {
-                params.insert(n.to_string(), parse_string(&value));
+                let value = parse_string(&value);
+                match params.entry(n.to_string()) {
+                    Entry::Vacant(entry) => {
+                        entry.insert(value);
+                    }
+                    Entry::Occupied(mut entry) => match entry.get_mut() {
+                        Value::Array(values) => values.push(value),
+                        prev => *prev = Value::Array(vec![prev.take(), value]),
+                    },
+                }
             }
         }
     }
     let method_name = method.ok_or(Error::InvalidData("the method is missing".into()))?;
+    let params = positional_params(&params).unwrap_or_else(|| json!(params));
     #[cfg(feature = "canonical")]
     let method = serde_json::from_value(json!({
         "method": method_name,
@@ -130,6 +167,19 @@ fn request_from_query_string<M: DeserializeOwned + Serialize>(
     }
 }
 
+/// Converts params with index names (`0`, `1`, ...) into an array
+fn positional_params(params: &BTreeMap<String, Value>) -> Option<Value> {
+    if params.is_empty() {
+        return None;
+    }
+    let mut values = vec![Value::Null; params.len()];
+    for (name, value) in params {
+        let pos: usize = name.parse().ok()?;
+        *values.get_mut(pos)? = value.clone();
+    }
+    Some(Value::Array(values))
+}
+
 fn value_to_string(field: &str, value: &Value) -> Result<String, Error> {
     Ok(match value {
         Value::Null => "null".to_string(),
@@ -145,10 +195,36 @@ fn value_to_string(field: &str, value: &Value) -> Result<String, Error> {
     })
 }
 
+/// Percent-encodes spaces, `%` and bytes outside of visible ASCII, so the result is always a
+/// valid header value
+fn percent_encode_header(value: &str) -> String {
+    let mut encoded = String::with_capacity(value.len());
+    for b in value.bytes() {
+        if b.is_ascii_graphic() && b != b'%' {
+            encoded.push(char::from(b));
+        } else {
+            let _ = write!(encoded, "%{:02X}", b);
+        }
+    }
+    encoded
+}
+
+fn push_param(pairs: &mut Vec<(String, String)>, name: String, value: &Value) -> Result<(), Error> {
+    if let Value::Array(values) = value {
+        for value in values {
+            pairs.push((name.clone(), value_to_string(&name, value)?));
+        }
+    } else {
+        let value = value_to_string(&name, value)?;
+        pairs.push((name, value));
+    }
+    Ok(())
+}
+
 fn request_into_query_string<M: Serialize>(req: &Request<M>) -> Result<String, Error> {
-    let mut pairs = Vec::new();
+    let mut pairs: Vec<(String, String)> = Vec::new();
     if let Some(id) = &req.id {
-        pairs.push(("i", id.to_string()));
+        pairs.push(("i".to_owned(), id.to_string()));
     }
     let req_value = serde_json::to_value(&req.method)?;
     let req_map = req_value
@@ -156,20 +232,28 @@ fn request_into_query_string<M: Serialize>(req: &Request<M>) -> Result<String, E
         .ok_or(Error::InvalidData("invalid request".into()))?;
     let method = req_map
         .get("method")
+        .or_else(|| req_map.get("m"))
         .ok_or(Error::InvalidData("method is missing".into()))?;
     pairs.push((
-        "m",
+        "m".to_owned(),
         method
             .as_str()
             .ok_or(Error::InvalidData("invalid method name".into()))?
             .to_string(),
     ));
-    if let Some(params) = req_map.get("params") {
-        let params = params
-            .as_object()
-            .ok_or(Error::InvalidData("params must be object".into()))?;
-        for (name, value) in params {
-            pairs.push((name, value_to_string(name, value)?));
+    if let Some(params) = req_map.get("params").or_else(|| req_map.get("p")) {
+        match params {
+            Value::Object(params) => {
+                for (name, value) in params {
+                    push_param(&mut pairs, name.clone(), value)?;
+                }
+            }
+            Value::Array(params) => {
+                for (pos, value) in params.iter().enumerate() {
+                    push_param(&mut pairs, pos.to_string(), value)?;
+                }
+            }
+            _ => return Err(Error::InvalidData("params must be object or array".into())),
         }
     }
     Ok(url::form_urlencoded::Serializer::new(String::new())
@@ -177,17 +261,114 @@ fn request_into_query_string<M: Serialize>(req: &Request<M>) -> Result<String, E
         .finish())
 }
 
+/// The default call id header name of [`HttpResponse`]
+pub const DEFAULT_ID_HEADER: &str = "X-JSONRPC-ID";
+
+const BATCH_COUNT_HEADER: &str = "X-JSONRPC-COUNT";
+
+fn default_id_header() -> header::HeaderName {
+    // header names are case-insensitive, static names must be lowercase
+    header::HeaderName::from_static("x-jsonrpc-id")
+}
+
 #[derive(Debug)]
 #[allow(clippy::module_name_repetitions)]
-/// A minimalistic HTTP response (no JSON RPC version, call id is placed to `X-JSONRPC-ID` header)
+/// A minimalistic HTTP response (no JSON RPC version, call id is placed to `X-JSONRPC-ID` header,
+/// see [`HttpResponse::with_id_header`]). String ids are percent-encoded in the header (spaces,
+/// `%` and bytes outside of visible ASCII)
 pub struct HttpResponse {
     status: http::StatusCode,
     headers: http::header::HeaderMap,
     body: String,
+    id_header: header::HeaderName,
 }
 
 impl HttpResponse {
-    /// HTTP status code (200 for success, 500 for error)
+    /// Create a new HTTP response from a JSON-RPC response, the HTTP status for errors is
+    /// provided by the mapper function (200 is always used for success)
+    pub fn from_response_with<R, F>(response: Response<R>, mapper: F) -> Result<Self, Error>
+    where
+        R: Serialize,
+        F: Fn(&RpcErrorKind) -> StatusCode,
+    {
+        let (id, res) = response.into_parts();
+        Self::from_parts_with(&id, &res, mapper)
+    }
+    /// Create a new HTTP response from JSON-RPC response parts (see [`Response::into_parts`])
+    pub fn from_handler_response<R: Serialize>(
+        id: &Id,
+        res: &HandlerResponse<R>,
+    ) -> Result<Self, Error> {
+        Self::from_parts_with(id, res, default_status_for)
+    }
+    fn from_parts_with<R, F>(id: &Id, res: &HandlerResponse<R>, mapper: F) -> Result<Self, Error>
+    where
+        R: Serialize,
+        F: Fn(&RpcErrorKind) -> StatusCode,
+    {
+        let status = match res {
+            HandlerResponse::Ok(_) => StatusCode::OK,
+            HandlerResponse::Err(e) => mapper(&e.kind()),
+        };
+        let mut headers = header::HeaderMap::new();
+        headers.insert(
+            header::CONTENT_TYPE,
+            header::HeaderValue::from_static(dataformat::Json::CONTENT_TYPE),
+        );
+        let id_value = match id {
+            Value::String(s) => percent_encode_header(s),
+            _ => value_to_string("", id)?,
+        };
+        let id_header = default_id_header();
+        headers.insert(
+            id_header.clone(),
+            id_value.parse().map_err(|e| {
+                Error::InvalidData(format!("failed to parse id as http header: {}", e))
+            })?,
+        );
+        Ok(HttpResponse {
+            status,
+            headers,
+            body: serde_json::to_string(res)?,
+            id_header,
+        })
+    }
+    /// Create a new HTTP response from batch responses. Unlike single responses, the body
+    /// contains complete JSON-RPC response objects (with ids), the status is always 200 (as
+    /// batches may mix results and errors), the call id header is omitted and `X-JSONRPC-COUNT`
+    /// header contains the number of responses. An empty batch produces 204 (No Content)
+    pub fn from_batch<R: Serialize>(responses: Vec<Response<R>>) -> Result<Self, Error> {
+        let mut headers = header::HeaderMap::new();
+        if responses.is_empty() {
+            return Ok(HttpResponse {
+                status: StatusCode::NO_CONTENT,
+                headers,
+                body: String::new(),
+                id_header: default_id_header(),
+            });
+        }
+        headers.insert(
+            header::CONTENT_TYPE,
+            header::HeaderValue::from_static(dataformat::Json::CONTENT_TYPE),
+        );
+        headers.insert(BATCH_COUNT_HEADER, responses.len().into());
+        Ok(HttpResponse {
+            status: StatusCode::OK,
+            headers,
+            body: serde_json::to_string(&responses)?,
+            id_header: default_id_header(),
+        })
+    }
+    /// Move the call id to a header with the given name (e.g. if a reverse proxy strips `X-`
+    /// headers)
+    pub fn with_id_header(mut self, name: header::HeaderName) -> Self {
+        if let Some(value) = self.headers.remove(&self.id_header) {
+            self.headers.insert(name.clone(), value);
+        }
+        self.id_header = name;
+        self
+    }
+    /// HTTP status code (200 for success, see [`default_status_for`] for errors)
     pub fn status(&self) -> http::StatusCode {
         self.status
     }
@@ -213,27 +394,174 @@ impl<R: Serialize> TryFrom<Response<R>> for HttpResponse {
     type Error = Error;
 
     fn try_from(response: Response<R>) -> Result<Self, Self::Error> {
-        let (id, res) = response.into_parts();
-        let status = if res.is_ok() {
-            StatusCode::OK
-        } else {
+        HttpResponse::from_response_with(response, default_status_for)
+    }
+}
+
+/// The default HTTP status mapping for JSON-RPC errors: 404 for method-not-found, 400 for parse
+/// errors, invalid requests and invalid params, 500 for the rest
+pub fn default_status_for(kind: &RpcErrorKind) -> StatusCode {
+    match kind {
+        RpcErrorKind::MethodNotFound => StatusCode::NOT_FOUND,
+        RpcErrorKind::ParseError | RpcErrorKind::InvalidRequest | RpcErrorKind::InvalidParams => {
+            StatusCode::BAD_REQUEST
+        }
+        RpcErrorKind::InternalError | RpcErrorKind::ServerError(_) | RpcErrorKind::Custom(_) => {
             StatusCode::INTERNAL_SERVER_ERROR
-        };
-        let mut headers = header::HeaderMap::new();
-        headers.insert(
-            header::CONTENT_TYPE,
-            header::HeaderValue::from_static("application/json"),
+        }
+    }
+}
+
+/// Data format negotiated from HTTP headers (see [`negotiate`])
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum NegotiatedFormat {
+    /// JSON
+    Json,
+    #[cfg(feature = "msgpack")]
+    /// MessagePack
+    Msgpack,
+}
+
+impl NegotiatedFormat {
+    fn from_mime(mime: &str) -> Option<Self> {
+        let mime = mime.split(';').next().unwrap_or_default().trim();
+        match mime.to_ascii_lowercase().as_str() {
+            dataformat::Json::CONTENT_TYPE => Some(NegotiatedFormat::Json),
+            #[cfg(feature = "msgpack")]
+            "application/msgpack" | "application/x-msgpack" | "application/vnd.msgpack" => {
+                Some(NegotiatedFormat::Msgpack)
+            }
+            _ => None,
+        }
+    }
+    /// Content type of the format
+    pub fn content_type(self) -> header::HeaderValue {
+        match self {
+            NegotiatedFormat::Json => {
+                header::HeaderValue::from_static(dataformat::Json::CONTENT_TYPE)
+            }
+            #[cfg(feature = "msgpack")]
+            NegotiatedFormat::Msgpack => {
+                header::HeaderValue::from_static(dataformat::Msgpack::CONTENT_TYPE)
+            }
+        }
+    }
+    /// Handle a request payload with the negotiated format (see
+    /// [`RpcServer::handle_request_payload`]), the response payload is encoded with the same
+    /// format
+    pub fn handle_request_payload<'a, RPC, M, SRC, R>(
+        self,
+        server: &'a RpcServer<'a, RPC, M, SRC, R>,
+        payload: &'a [u8],
+        source: SRC,
+    ) -> Option<Vec<u8>>
+    where
+        RPC: RpcServerHandler<'a, Method = M, Result = R, Source = SRC>,
+        M: serde::Deserialize<'a> + 'a,
+        R: Serialize + 'a,
+        SRC: fmt::Debug,
+    {
+        match self {
+            NegotiatedFormat::Json => {
+                server.handle_request_payload::<dataformat::Json>(payload, source)
+            }
+            #[cfg(feature = "msgpack")]
+            NegotiatedFormat::Msgpack => {
+                server.handle_request_payload::<dataformat::Msgpack>(payload, source)
+            }
+        }
+    }
+}
+
+/// Negotiate the data format from `Content-Type` (checked first) and `Accept` headers. JSON is
+/// used if the headers are absent or no supported format is found
+pub fn negotiate(headers: &header::HeaderMap) -> NegotiatedFormat {
+    headers
+        .get_all(header::CONTENT_TYPE)
+        .iter()
+        .chain(headers.get_all(header::ACCEPT).iter())
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(','))
+        .find_map(NegotiatedFormat::from_mime)
+        .unwrap_or(NegotiatedFormat::Json)
+}
+
+#[cfg(test)]
+mod tests {
+    use serde::Deserialize;
+
+    use super::*;
+
+    #[derive(Serialize, Deserialize, Debug, PartialEq)]
+    #[cfg_attr(feature = "canonical", serde(tag = "method", content = "params"))]
+    #[cfg_attr(not(feature = "canonical"), serde(tag = "m", content = "p"))]
+    enum TestMethod {
+        #[serde(rename = "sum")]
+        Sum { n: Vec<i64> },
+        #[serde(rename = "nested")]
+        Nested { n: Vec<Vec<i64>> },
+        #[serde(rename = "add")]
+        Add(i64, i64),
+    }
+
+    #[test]
+    fn test_query_string_array_params() {
+        let req: Request<TestMethod> = QueryString::new("i=1&m=sum&n=1&n=2").try_into().unwrap();
+        let (id, method) = req.into_parts();
+        assert_eq!(id, Some(Id::from(1)));
+        assert_eq!(method, TestMethod::Sum { n: vec![1, 2] });
+        let qs =
+            QueryString::try_from(Request::new(Id::from(1), TestMethod::Sum { n: vec![1, 2] }))
+                .unwrap();
+        assert_eq!(qs.as_ref(), "i=1&m=sum&n=1&n=2");
+        let err = QueryString::try_from(Request::new0(TestMethod::Nested {
+            n: vec![vec![1], vec![2]],
+        }))
+        .unwrap_err();
+        assert!(matches!(err, Error::InvalidData(_)));
+    }
+
+    #[test]
+    fn test_parse_string_numbers() {
+        assert_eq!(parse_string("42"), json!(42));
+        assert_eq!(parse_string("-42"), json!(-42));
+        assert_eq!(parse_string("1.5"), json!(1.5));
+        let big = parse_string("99999999999999999999");
+        if cfg!(feature = "preserve-big-int") {
+            assert_eq!(big, json!("99999999999999999999"));
+        } else {
+            assert_eq!(big, json!(1e20));
+        }
+        // infinity is not a valid JSON number
+        assert_eq!(parse_string("1e999"), json!("1e999"));
+        assert_eq!(parse_string("NaN"), json!("NaN"));
+    }
+
+    #[test]
+    fn test_id_header() {
+        let response: Response<u32> =
+            Response::from_handler_response(Id::from("a b%"), HandlerResponse::Ok(1));
+        let http_response = HttpResponse::try_from(response).unwrap();
+        assert_eq!(
+            http_response.headers().get(DEFAULT_ID_HEADER).unwrap(),
+            "a%20b%25"
         );
-        headers.insert(
-            "X-JSONRPC-ID",
-            value_to_string("", &id)?.parse().map_err(|e| {
-                Error::InvalidData(format!("failed to parse id as http header: {}", e))
-            })?,
+        let http_response =
+            http_response.with_id_header(header::HeaderName::from_static("jsonrpc-id"));
+        assert!(http_response.headers().get(DEFAULT_ID_HEADER).is_none());
+        assert_eq!(
+            http_response.headers().get("jsonrpc-id").unwrap(),
+            "a%20b%25"
         );
-        Ok(HttpResponse {
-            status,
-            headers,
-            body: serde_json::to_string(&res)?,
-        })
+    }
+
+    #[test]
+    fn test_query_string_positional_params() {
+        let qs = QueryString::try_from(Request::new0(TestMethod::Add(1, 2))).unwrap();
+        assert_eq!(qs.as_ref(), "m=add&0=1&1=2");
+        let req: Request<TestMethod> = qs.try_into().unwrap();
+        let (id, method) = req.into_parts();
+        assert_eq!(id, None);
+        assert_eq!(method, TestMethod::Add(1, 2));
     }
 }
@@ -1,3 +1,66 @@
+#[cfg(feature = "axum")]
+/// [axum](https://docs.rs/axum) integration
+pub mod axum;
+#[cfg(feature = "std")]
+/// Length-delimited framing for stream transports
+pub mod codec;
+#[cfg(feature = "std")]
+/// Requests and responses with methods and results known at runtime only (JSON)
+pub mod dynamic;
 #[cfg(feature = "http")]
 /// HTTP tools
 pub mod http;
+#[cfg(feature = "std")]
+/// Newline-delimited JSON (NDJSON) requests and responses
+pub mod ndjson;
+#[cfg(feature = "std")]
+/// Per-method and per-source call rate limiting
+pub mod ratelimit;
+#[cfg(feature = "schemars")]
+/// JSON Schema of request and response objects
+pub mod schema;
+#[cfg(feature = "std")]
+/// WebSocket tools
+pub mod ws;
+
+#[cfg(feature = "std")]
+/// Split a JSON batch payload (a top-level array of requests) into individually packed request
+/// payloads, e.g. to route batch elements to different backends. The elements are not
+/// validated, ids (if any) are kept as-is.
+pub fn split_batch(payload: &[u8]) -> Result<Vec<Vec<u8>>, serde_json::Error> {
+    let batch: Vec<serde_json::Value> = serde_json::from_slice(payload)?;
+    batch.iter().map(serde_json::to_vec).collect()
+}
+
+#[cfg(feature = "std")]
+/// Parse the method name only (`method` or `m` field) from a JSON request payload, without
+/// de-serializing params. Returns `None` if the payload is not a valid JSON object or has no
+/// method.
+pub fn peek_method(payload: &[u8]) -> Option<String> {
+    serde_json::from_slice::<crate::request::MethodPeek>(payload)
+        .ok()?
+        .method
+        .map(Into::into)
+}
+
+#[cfg(all(test, feature = "std"))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_split_batch() {
+        let payload =
+            br#"[{"i":1,"m":"test","p":{}},{"m":"notify","p":{"a":[1,2]}},{"i":"x","m":"test"}]"#;
+        let split = split_batch(payload).unwrap();
+        assert_eq!(split.len(), 3);
+        let requests: Vec<serde_json::Value> = split
+            .iter()
+            .map(|payload| serde_json::from_slice(payload).unwrap())
+            .collect();
+        assert_eq!(requests[0]["i"], 1);
+        assert!(requests[1].get("i").is_none());
+        assert_eq!(requests[1]["p"]["a"], serde_json::json!([1, 2]));
+        assert_eq!(requests[2]["i"], "x");
+        assert!(split_batch(br#"{"i":1}"#).is_err());
+    }
+}
@@ -0,0 +1,36 @@
+use serde::{Deserialize, Serialize};
+
+use crate::{request::Request, response::Response, RpcId};
+
+/// Encode requests as newline-delimited JSON, one request per line (with no trailing newline)
+pub fn encode_requests<M, I>(
+    requests: impl IntoIterator<Item = Request<M, I>>,
+) -> Result<Vec<u8>, serde_json::Error>
+where
+    M: Serialize,
+    I: Serialize,
+{
+    let mut payload = Vec::new();
+    for (n, request) in requests.into_iter().enumerate() {
+        if n > 0 {
+            payload.push(b'\n');
+        }
+        serde_json::to_writer(&mut payload, &request)?;
+    }
+    Ok(payload)
+}
+
+/// Decode newline-delimited JSON responses, one response per line. Blank lines are skipped, `\r\n`
+/// line endings are accepted
+pub fn decode_responses<'a, R, I>(
+    payload: &'a [u8],
+) -> impl Iterator<Item = Result<Response<R, I>, serde_json::Error>> + 'a
+where
+    R: Deserialize<'a> + 'a,
+    I: RpcId + 'a,
+{
+    payload
+        .split(|b| *b == b'\n')
+        .filter(|line| !line.iter().all(u8::is_ascii_whitespace))
+        .map(serde_json::from_slice)
+}
@@ -0,0 +1,192 @@
+use std::{
+    collections::HashMap,
+    fmt,
+    sync::{Mutex, PoisonError},
+    time::{Duration, Instant},
+};
+
+use crate::{RpcError, RpcErrorKind, RpcResult};
+
+/// Error code returned when a call is rate-limited ("too many requests", a server error code, see
+/// [`RpcErrorKind::ServerError`])
+pub const ERR_CODE_TOO_MANY_REQUESTS: i32 = -32029;
+
+/// The default maximum number of buckets (see [`RateLimiter::with_max_buckets`])
+pub const DEFAULT_MAX_BUCKETS: usize = 4096;
+
+/// Token bucket limit: `burst` calls at once, refilled at `burst` calls per `period`
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Limit {
+    burst: u32,
+    period: Duration,
+}
+
+impl Limit {
+    /// Create a new limit, e.g. `Limit::new(10, Duration::from_secs(1))` for 10 calls per second
+    pub fn new(burst: u32, period: Duration) -> Self {
+        Self { burst, period }
+    }
+    fn refill(&self, elapsed: Duration) -> f64 {
+        if self.period.is_zero() {
+            return f64::from(self.burst);
+        }
+        elapsed.as_secs_f64() * f64::from(self.burst) / self.period.as_secs_f64()
+    }
+}
+
+struct Bucket {
+    tokens: f64,
+    updated: Instant,
+}
+
+impl Bucket {
+    fn refill(&mut self, limit: &Limit, now: Instant) {
+        let elapsed = now.saturating_duration_since(self.updated);
+        self.tokens = (self.tokens + limit.refill(elapsed)).min(f64::from(limit.burst));
+        self.updated = now;
+    }
+    fn is_full(&self, limit: &Limit) -> bool {
+        self.tokens >= f64::from(limit.burst)
+    }
+}
+
+/// Thread-safe token bucket rate limiter, keyed by the method name and the call source. Calls of
+/// methods with no limit configured (and no default limit set) are not limited. Use
+/// [`RateLimiter::check`] in [`crate::server::RpcServerHandler::before_call`]
+pub struct RateLimiter {
+    limits: HashMap<String, Limit>,
+    default_limit: Option<Limit>,
+    max_buckets: usize,
+    buckets: Mutex<HashMap<(String, String), Bucket>>,
+}
+
+impl Default for RateLimiter {
+    fn default() -> Self {
+        Self {
+            limits: HashMap::new(),
+            default_limit: None,
+            max_buckets: DEFAULT_MAX_BUCKETS,
+            buckets: Mutex::default(),
+        }
+    }
+}
+
+impl RateLimiter {
+    /// Create a new rate limiter with no limits
+    pub fn new() -> Self {
+        Self::default()
+    }
+    /// Set the maximum number of buckets (method/source pairs), the default is
+    /// [`DEFAULT_MAX_BUCKETS`]. When the maximum is reached, full (refilled) buckets are purged,
+    /// which does not affect limiting. If there are still no free slots, calls from new sources
+    /// are rejected until some of the buckets are refilled
+    pub fn with_max_buckets(mut self, max_buckets: usize) -> Self {
+        self.max_buckets = max_buckets;
+        self
+    }
+    /// Set a limit for the method
+    pub fn with_limit(mut self, method: impl Into<String>, limit: Limit) -> Self {
+        self.limits.insert(method.into(), limit);
+        self
+    }
+    /// Set a limit for methods with no own limit
+    pub fn with_default_limit(mut self, limit: Limit) -> Self {
+        self.default_limit = Some(limit);
+        self
+    }
+    /// Take a token for the method call from the source, returns
+    /// [`ERR_CODE_TOO_MANY_REQUESTS`] error if the bucket is exhausted
+    pub fn check<S: fmt::Debug>(&self, method: &str, source: &S) -> RpcResult<()> {
+        self.check_at(method, source, Instant::now())
+    }
+    /// Same as [`RateLimiter::check`] but with the given current time
+    pub fn check_at<S: fmt::Debug>(&self, method: &str, source: &S, now: Instant) -> RpcResult<()> {
+        let Some(limit) = self.limit(method) else {
+            return Ok(());
+        };
+        let too_many_requests = || {
+            RpcError::new(
+                RpcErrorKind::Custom(ERR_CODE_TOO_MANY_REQUESTS),
+                "too many requests".to_owned(),
+            )
+        };
+        let mut buckets = self.buckets.lock().unwrap_or_else(PoisonError::into_inner);
+        let key = (method.to_owned(), format!("{:?}", source));
+        if buckets.len() >= self.max_buckets && !buckets.contains_key(&key) {
+            buckets.retain(|(method, _), bucket| {
+                self.limit(method).is_some_and(|limit| {
+                    bucket.refill(limit, now);
+                    !bucket.is_full(limit)
+                })
+            });
+            if buckets.len() >= self.max_buckets {
+                return Err(too_many_requests());
+            }
+        }
+        let bucket = buckets.entry(key).or_insert_with(|| Bucket {
+            tokens: f64::from(limit.burst),
+            updated: now,
+        });
+        bucket.refill(limit, now);
+        if bucket.tokens < 1.0 {
+            return Err(too_many_requests());
+        }
+        bucket.tokens -= 1.0;
+        Ok(())
+    }
+    /// Remove buckets which have not been used for the given duration (e.g. of disconnected
+    /// sources)
+    pub fn purge_idle(&self, idle: Duration) {
+        let now = Instant::now();
+        self.buckets
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .retain(|_, bucket| now.saturating_duration_since(bucket.updated) < idle);
+    }
+    fn limit(&self, method: &str) -> Option<&Limit> {
+        self.limits.get(method).or(self.default_limit.as_ref())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::{Duration, Instant};
+
+    use super::{Limit, RateLimiter, ERR_CODE_TOO_MANY_REQUESTS};
+    use crate::RpcErrorKind;
+
+    #[test]
+    fn test_limit() {
+        let limiter = RateLimiter::new().with_limit("test", Limit::new(2, Duration::from_secs(1)));
+        let now = Instant::now();
+        assert!(limiter.check_at("test", &"a", now).is_ok());
+        assert!(limiter.check_at("test", &"a", now).is_ok());
+        assert_eq!(
+            limiter.check_at("test", &"a", now).unwrap_err().kind(),
+            RpcErrorKind::Custom(ERR_CODE_TOO_MANY_REQUESTS)
+        );
+        // other sources and methods with no limit
+        assert!(limiter.check_at("test", &"b", now).is_ok());
+        assert!(limiter.check_at("other", &"a", now).is_ok());
+        let now = now + Duration::from_millis(500);
+        assert!(limiter.check_at("test", &"a", now).is_ok());
+        assert!(limiter.check_at("test", &"a", now).is_err());
+    }
+
+    #[test]
+    fn test_max_buckets() {
+        let limiter = RateLimiter::new()
+            .with_default_limit(Limit::new(2, Duration::from_secs(1)))
+            .with_max_buckets(2);
+        let now = Instant::now();
+        assert!(limiter.check_at("test", &"a", now).is_ok());
+        assert!(limiter.check_at("test", &"b", now).is_ok());
+        // no full buckets to purge
+        assert!(limiter.check_at("test", &"c", now).is_err());
+        assert!(limiter.check_at("test", &"a", now).is_ok());
+        // the buckets are refilled and purged
+        let now = now + Duration::from_secs(1);
+        assert!(limiter.check_at("test", &"c", now).is_ok());
+        assert_eq!(limiter.buckets.lock().unwrap().len(), 1);
+    }
+}
@@ -0,0 +1,193 @@
+use schemars::{
+    gen::SchemaGenerator,
+    schema::{
+        InstanceType, ObjectValidation, RootSchema, Schema, SchemaObject, SingleOrVec,
+        SubschemaValidation,
+    },
+    JsonSchema,
+};
+
+use crate::field_names::{ERROR, ID, JSONRPC, METHOD, RESULT};
+
+/// JSON Schema of request objects for the method type (the method schema is applied to the whole
+/// request, as methods are flattened into it). Keys follow the active (canonical or compact)
+/// layout
+pub fn request_schema<M: JsonSchema>() -> RootSchema {
+    let mut gen = SchemaGenerator::default();
+    let method_schema = gen.subschema_for::<M>();
+    let mut object = ObjectValidation::default();
+    object
+        .properties
+        .insert(JSONRPC.to_owned(), version_schema());
+    object.properties.insert(ID.to_owned(), id_schema());
+    object
+        .properties
+        .insert(METHOD.to_owned(), typed_schema(InstanceType::String).into());
+    object.required.insert(METHOD.to_owned());
+    if cfg!(all(feature = "canonical", feature = "strict-version")) {
+        object.required.insert(JSONRPC.to_owned());
+    }
+    let schema = SchemaObject {
+        instance_type: Some(InstanceType::Object.into()),
+        object: Some(Box::new(object)),
+        subschemas: Some(Box::new(SubschemaValidation {
+            all_of: Some(vec![method_schema]),
+            ..SubschemaValidation::default()
+        })),
+        ..SchemaObject::default()
+    };
+    into_root_schema(gen, schema)
+}
+
+/// JSON Schema of response objects for the result type, a response contains either a result or an
+/// error. Keys follow the active (canonical or compact) layout
+pub fn response_schema<R: JsonSchema>() -> RootSchema {
+    let mut gen = SchemaGenerator::default();
+    let result_schema = gen.subschema_for::<R>();
+    let mut object = ObjectValidation::default();
+    object
+        .properties
+        .insert(JSONRPC.to_owned(), version_schema());
+    object.properties.insert(ID.to_owned(), id_schema());
+    object.properties.insert(RESULT.to_owned(), result_schema);
+    object.properties.insert(ERROR.to_owned(), error_schema());
+    object.required.insert(ID.to_owned());
+    if cfg!(all(feature = "canonical", feature = "strict-version")) {
+        object.required.insert(JSONRPC.to_owned());
+    }
+    let schema = SchemaObject {
+        instance_type: Some(InstanceType::Object.into()),
+        object: Some(Box::new(object)),
+        subschemas: Some(Box::new(SubschemaValidation {
+            one_of: Some(vec![required_schema(RESULT), required_schema(ERROR)]),
+            ..SubschemaValidation::default()
+        })),
+        ..SchemaObject::default()
+    };
+    into_root_schema(gen, schema)
+}
+
+fn into_root_schema(mut gen: SchemaGenerator, schema: SchemaObject) -> RootSchema {
+    RootSchema {
+        meta_schema: gen.settings().meta_schema.clone(),
+        definitions: gen.take_definitions(),
+        schema,
+    }
+}
+
+fn typed_schema(instance_type: InstanceType) -> SchemaObject {
+    SchemaObject {
+        instance_type: Some(instance_type.into()),
+        ..SchemaObject::default()
+    }
+}
+
+fn version_schema() -> Schema {
+    SchemaObject {
+        const_value: Some(crate::JSONRPC_VERSION.into()),
+        ..typed_schema(InstanceType::String)
+    }
+    .into()
+}
+
+fn id_schema() -> Schema {
+    SchemaObject {
+        instance_type: Some(SingleOrVec::Vec(vec![
+            InstanceType::String,
+            InstanceType::Number,
+            InstanceType::Null,
+        ])),
+        ..SchemaObject::default()
+    }
+    .into()
+}
+
+fn error_schema() -> Schema {
+    let mut object = ObjectValidation::default();
+    object.properties.insert(
+        "code".to_owned(),
+        typed_schema(InstanceType::Integer).into(),
+    );
+    object.properties.insert(
+        "message".to_owned(),
+        typed_schema(InstanceType::String).into(),
+    );
+    object
+        .properties
+        .insert("data".to_owned(), Schema::Bool(true));
+    object.required.insert("code".to_owned());
+    SchemaObject {
+        object: Some(Box::new(object)),
+        ..typed_schema(InstanceType::Object)
+    }
+    .into()
+}
+
+fn required_schema(field: &str) -> Schema {
+    let mut object = ObjectValidation::default();
+    object.required.insert(field.to_owned());
+    SchemaObject {
+        object: Some(Box::new(object)),
+        ..SchemaObject::default()
+    }
+    .into()
+}
+
+#[cfg(test)]
+mod tests {
+    use schemars::{schema::RootSchema, JsonSchema};
+    use serde::{Deserialize, Serialize};
+
+    use super::{request_schema, response_schema};
+    use crate::field_names::{ERROR, ID, JSONRPC, METHOD, RESULT};
+
+    #[derive(Serialize, Deserialize, JsonSchema)]
+    #[cfg_attr(feature = "canonical", serde(tag = "method", content = "params"))]
+    #[cfg_attr(not(feature = "canonical"), serde(tag = "m", content = "p"))]
+    enum TestMethod {
+        #[serde(rename = "add")]
+        Add { a: i64, b: i64 },
+    }
+
+    fn properties(schema: &RootSchema) -> Vec<&str> {
+        schema
+            .schema
+            .object
+            .as_ref()
+            .unwrap()
+            .properties
+            .keys()
+            .map(String::as_str)
+            .collect()
+    }
+
+    #[test]
+    fn test_request_schema() {
+        let schema = request_schema::<TestMethod>();
+        let properties = properties(&schema);
+        for field in [JSONRPC, ID, METHOD] {
+            assert!(properties.contains(&field), "{} is missing", field);
+        }
+        if cfg!(feature = "canonical") {
+            assert_eq!((ID, METHOD), ("id", "method"));
+        }
+        let object = schema.schema.object.as_ref().unwrap();
+        assert!(object.required.contains(METHOD));
+        let all_of = schema.schema.subschemas.as_ref().unwrap().all_of.as_ref();
+        assert_eq!(all_of.unwrap().len(), 1);
+    }
+
+    #[test]
+    fn test_response_schema() {
+        let schema = response_schema::<i64>();
+        let properties = properties(&schema);
+        for field in [JSONRPC, ID, RESULT, ERROR] {
+            assert!(properties.contains(&field), "{} is missing", field);
+        }
+        let object = schema.schema.object.as_ref().unwrap();
+        assert!(object.required.contains(ID));
+        // either a result or an error
+        let one_of = schema.schema.subschemas.as_ref().unwrap().one_of.as_ref();
+        assert_eq!(one_of.unwrap().len(), 2);
+    }
+}
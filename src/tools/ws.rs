@@ -0,0 +1,42 @@
+use crate::dataformat::DataFormat;
+
+/// WebSocket frame for a packed payload
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum WsFrame {
+    /// Text frame (text data formats, e.g. JSON)
+    Text(String),
+    /// Binary frame (binary data formats, e.g. MessagePack)
+    Binary(Vec<u8>),
+}
+
+impl WsFrame {
+    /// Get the frame payload
+    pub fn as_bytes(&self) -> &[u8] {
+        match self {
+            WsFrame::Text(s) => s.as_bytes(),
+            WsFrame::Binary(b) => b,
+        }
+    }
+}
+
+impl From<WsFrame> for Vec<u8> {
+    fn from(frame: WsFrame) -> Self {
+        match frame {
+            WsFrame::Text(s) => s.into_bytes(),
+            WsFrame::Binary(b) => b,
+        }
+    }
+}
+
+/// Wrap a payload packed with the data format into a WebSocket frame of the matching type (see
+/// [`DataFormat::IS_TEXT`]). If a text payload is not a valid UTF-8, a binary frame is returned
+pub fn frame_for<D: DataFormat>(payload: Vec<u8>) -> WsFrame {
+    if D::IS_TEXT {
+        match String::from_utf8(payload) {
+            Ok(s) => WsFrame::Text(s),
+            Err(e) => WsFrame::Binary(e.into_bytes()),
+        }
+    } else {
+        WsFrame::Binary(payload)
+    }
+}